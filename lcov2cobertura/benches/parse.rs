@@ -0,0 +1,85 @@
+//! Compares sequential and parallel parsing of many large fake tracefiles, similar to the data
+//! `examples/fake_cobertura.rs` generates.
+//!
+//! `cargo bench -p lcov2cobertura --features parallel -- [FILES] [RECORDS_PER_FILE]`
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use lcov2cobertura::{parse_file_with, parse_files_parallel, CoverageData, ParseOptions};
+
+/// Writes `files` tracefiles of `records` source files with 500 lines and 20 functions each
+fn generate_tracefiles(dir: &Path, files: usize, records: usize) -> anyhow::Result<Vec<PathBuf>> {
+    std::fs::create_dir_all(dir)?;
+    let mut paths = vec![];
+    for file in 0..files {
+        let mut lcov = String::new();
+        for record in 0..records {
+            writeln!(
+                lcov,
+                "SF:crate{file}/src/module{}/file{record}.rs",
+                record % 10
+            )?;
+            for function in 0..20 {
+                writeln!(
+                    lcov,
+                    "FN:{},_RNvCs1234_5crate8function{function}",
+                    function * 25 + 1
+                )?;
+                writeln!(
+                    lcov,
+                    "FNDA:{},_RNvCs1234_5crate8function{function}",
+                    function % 3
+                )?;
+            }
+            for line in 1..=500 {
+                writeln!(lcov, "DA:{line},{}", (line * 7 + record) % 5)?;
+                if line % 10 == 0 {
+                    writeln!(lcov, "BRDA:{line},0,0,{}", line % 3)?;
+                    writeln!(lcov, "BRDA:{line},0,1,0")?;
+                }
+            }
+            lcov.push_str("end_of_record\n");
+        }
+        let path = dir.join(format!("lcov{file}.info"));
+        std::fs::write(&path, lcov)?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+fn main() -> anyhow::Result<()> {
+    let mut args = std::env::args()
+        .skip(1)
+        .filter(|a| !a.starts_with("--"))
+        .map(|a| a.parse::<usize>());
+    let files = args.next().transpose()?.unwrap_or(64);
+    let records = args.next().transpose()?.unwrap_or(100);
+
+    let dir = std::env::temp_dir().join(format!("lcov2cobertura-bench-{}", std::process::id()));
+    let paths = generate_tracefiles(&dir, files, records)?;
+    let options = ParseOptions::default();
+    let base_dir = Path::new(".");
+    println!("{files} tracefiles with {records} records each");
+
+    let start = Instant::now();
+    let mut sequential = CoverageData::default();
+    for path in &paths {
+        sequential.merge(parse_file_with(path.as_path(), base_dir, &[], &options)?);
+    }
+    let sequential_time = start.elapsed();
+    println!("sequential:      {sequential_time:?}");
+
+    for jobs in [Some(1), Some(2), Some(4), None] {
+        let start = Instant::now();
+        parse_files_parallel(&paths, base_dir, &[], &options, jobs)?;
+        let elapsed = start.elapsed();
+        println!(
+            "parallel {:>6}: {elapsed:?} ({:.2}x)",
+            jobs.map_or_else(|| "all".to_string(), |j| j.to_string()),
+            sequential_time.as_secs_f64() / elapsed.as_secs_f64()
+        );
+    }
+    std::fs::remove_dir_all(dir)?;
+    Ok(())
+}
@@ -0,0 +1,170 @@
+//! Reads cobertura XML back into [`CoverageData`], e.g. to compare two reports
+use std::io::BufRead;
+use std::path::Path;
+
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::reader::Reader;
+
+use crate::{Branch, Class, ClassKey, CoverageData};
+
+fn attr(elem: &BytesStart<'_>, name: &str) -> anyhow::Result<Option<String>> {
+    Ok(match elem.try_get_attribute(name)? {
+        Some(a) => Some(a.unescape_value()?.into_owned()),
+        None => None,
+    })
+}
+
+fn usize_attr(elem: &BytesStart<'_>, name: &str) -> anyhow::Result<usize> {
+    Ok(attr(elem, name)?.map_or(Ok(0), |v| v.parse())?)
+}
+
+/// Parses the `(covered/total)` part of `condition-coverage="50% (1/2)"`
+fn condition_coverage(value: &str) -> Option<(usize, usize)> {
+    let counts = value.split_once('(')?.1.strip_suffix(')')?;
+    let (covered, total) = counts.split_once('/')?;
+    Some((covered.parse().ok()?, total.parse().ok()?))
+}
+
+/// Element currently being read, only what is needed to place `line` elements
+#[derive(Default)]
+struct State {
+    package: String,
+    class_key: ClassKey,
+    method: Option<String>,
+    in_source: bool,
+}
+
+impl State {
+    fn class<'c>(&self, cov_data: &'c mut CoverageData) -> &'c mut Class {
+        cov_data
+            .packages
+            .entry(self.package.clone())
+            .or_default()
+            .classes
+            .entry(self.class_key.clone())
+            .or_insert_with(|| Class {
+                source: self.class_key.0,
+                ..Class::default()
+            })
+    }
+
+    fn start(&mut self, elem: &BytesStart<'_>, cov_data: &mut CoverageData) -> anyhow::Result<()> {
+        match elem.name().as_ref() {
+            b"source" => self.in_source = true,
+            b"package" => {
+                self.package = attr(elem, "name")?.unwrap_or_default();
+                cov_data.packages.entry(self.package.clone()).or_default();
+            }
+            b"class" => {
+                let file_name = attr(elem, "filename")?.unwrap_or_default();
+                // the `source` attribute written for several roots, the base directory otherwise
+                let source = attr(elem, "source")?
+                    .and_then(|root| cov_data.sources().position(|s| s == root))
+                    .unwrap_or(0);
+                self.class_key = (source, file_name);
+                let name = attr(elem, "name")?;
+                let class = self.class(cov_data);
+                if let Some(name) = name {
+                    class.name = name;
+                }
+            }
+            b"method" => self.method = Some(attr(elem, "name")?.unwrap_or_default()),
+            b"line" => {
+                let number = usize_attr(elem, "number")?;
+                let hits = usize_attr(elem, "hits")?;
+                if let Some(method) = &self.method {
+                    // method lines carry the function start line and hits
+                    self.class(cov_data)
+                        .methods
+                        .entry(method.clone())
+                        .or_insert((number, hits));
+                } else {
+                    let (branches_covered, branches_total) = attr(elem, "condition-coverage")?
+                        .as_deref()
+                        .and_then(condition_coverage)
+                        .unwrap_or_default();
+                    // a branch line without outcomes has no coverage to write back
+                    let branch =
+                        branches_total > 0 && attr(elem, "branch")?.is_some_and(|v| v == "true");
+                    // branch numbers are not written, the covered ones are numbered first
+                    let outcomes = (0..branches_total)
+                        .map(|i| ((0, i), usize::from(i < branches_covered)))
+                        .collect();
+                    let class = self.class(cov_data);
+                    class.lines.insert(
+                        number,
+                        Branch {
+                            branch,
+                            outcomes,
+                            hits,
+                        },
+                    );
+                    if hits > 0 {
+                        class.lines_covered += 1;
+                    }
+                }
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+/// parses cobertura XML from a reader, packages matching one of the `excludes` regexes are
+/// removed like in [`crate::parse_lines`]
+///
+/// # Errors
+///
+/// IO, XML or number parsing errors
+pub fn parse_cobertura<R: BufRead>(reader: R, excludes: &[&str]) -> anyhow::Result<CoverageData> {
+    let mut reader = Reader::from_reader(reader);
+    reader.config_mut().trim_text(true);
+    let mut cov_data = CoverageData::default();
+    let mut state = State::default();
+    let mut buf = Vec::new();
+    loop {
+        match reader.read_event_into(&mut buf) {
+            Err(e) => anyhow::bail!("Error at position {}: {:?}", reader.buffer_position(), e),
+            Ok(Event::Eof) => break,
+            Ok(Event::Start(e)) => state.start(&e, &mut cov_data)?,
+            Ok(Event::Empty(e)) => {
+                state.start(&e, &mut cov_data)?;
+                if e.name().as_ref() == b"method" {
+                    state.method = None;
+                }
+            }
+            Ok(Event::End(e)) => match e.name().as_ref() {
+                b"source" => state.in_source = false,
+                b"method" => state.method = None,
+                _ => (),
+            },
+            // the first source is the base directory, class file names are relative to it
+            // unless their `source` attribute names another one
+            Ok(Event::Text(e)) if state.in_source => {
+                let source = e.decode()?.into_owned();
+                if cov_data.base_dir.is_empty() {
+                    cov_data.base_dir = source;
+                } else {
+                    cov_data.source_roots.push(source);
+                }
+            }
+            Ok(_) => (),
+        }
+        buf.clear();
+    }
+    cov_data.remove_excluded(excludes)?;
+    Ok(cov_data)
+}
+
+/// parses cobertura XML from filename
+///
+/// # Errors
+///
+/// Either IO error or errors parsing the XML.
+pub fn parse_cobertura_file<P: AsRef<Path>>(
+    filename: P,
+    excludes: &[&str],
+) -> anyhow::Result<CoverageData> {
+    let file = std::fs::File::open(filename)?;
+    parse_cobertura(std::io::BufReader::new(file), excludes)
+}
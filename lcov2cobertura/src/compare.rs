@@ -0,0 +1,421 @@
+//! Coverage comparison between two reports, e.g. of the target branch and a merge request
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write as _;
+use std::str::FromStr;
+
+use crate::diff::line_ranges;
+use crate::{CompSummary, CoverageData, Metric, Summary};
+
+/// Output format of [`Comparison::render`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CompareFormat {
+    /// plain text, for job logs
+    #[default]
+    Text,
+    /// Markdown, for merge request comments
+    Markdown,
+    /// JSON, for further processing
+    Json,
+}
+
+impl FromStr for CompareFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "markdown" | "md" => Ok(Self::Markdown),
+            "json" => Ok(Self::Json),
+            _ => anyhow::bail!("unknown compare format {s:?}, expected text, markdown or json"),
+        }
+    }
+}
+
+/// Coverage of a package, file or the total before and after, `None` if it does not exist on
+/// that side
+#[derive(Debug, Clone)]
+pub struct SummaryDelta {
+    /// package name, relative file name or `total`
+    pub name: String,
+    /// summary in the old report
+    pub before: Option<Summary>,
+    /// summary in the new report
+    pub after: Option<Summary>,
+}
+
+impl SummaryDelta {
+    /// change of the rate in percentage points, `None` for added or removed entries
+    #[must_use]
+    pub fn delta(&self, metric: Metric) -> Option<f64> {
+        match (&self.before, &self.after) {
+            (Some(before), Some(after)) => Some((after.rate(metric) - before.rate(metric)) * 100.),
+            _ => None,
+        }
+    }
+
+    fn changed(&self) -> bool {
+        [Metric::Lines, Metric::Branches]
+            .into_iter()
+            .any(|metric| self.delta(metric) != Some(0.))
+    }
+}
+
+/// Result of [`compare`]
+#[derive(Debug, Clone)]
+pub struct Comparison {
+    /// total coverage
+    pub total: SummaryDelta,
+    /// all packages of both reports, sorted by name
+    pub packages: Vec<SummaryDelta>,
+    /// all files of both reports, sorted by name
+    pub files: Vec<SummaryDelta>,
+    /// per file the lines that were covered before and are uncovered now
+    pub newly_uncovered_lines: Vec<(String, Vec<usize>)>,
+    /// file and name of functions that were called before and are not called now
+    pub functions_lost: Vec<(String, String)>,
+}
+
+fn deltas(
+    mut before: BTreeMap<String, Summary>,
+    mut after: BTreeMap<String, Summary>,
+) -> Vec<SummaryDelta> {
+    let names: BTreeSet<String> = before.keys().chain(after.keys()).cloned().collect();
+    names
+        .into_iter()
+        .map(|name| SummaryDelta {
+            before: before.remove(&name),
+            after: after.remove(&name),
+            name,
+        })
+        .collect()
+}
+
+fn package_summaries(cov_data: &CoverageData) -> BTreeMap<String, Summary> {
+    cov_data
+        .packages
+        .iter()
+        .map(|(name, package)| (name.clone(), package.summary()))
+        .collect()
+}
+
+fn file_summaries(cov_data: &CoverageData) -> BTreeMap<String, Summary> {
+    cov_data
+        .packages
+        .values()
+        .flat_map(|package| package.classes.iter())
+        .map(|(key, class)| (cov_data.class_path(key), class.summary()))
+        .collect()
+}
+
+/// Compares the coverage of two reports, files are matched by their relative file name
+#[must_use]
+pub fn compare(before: &CoverageData, after: &CoverageData) -> Comparison {
+    let after_classes: BTreeMap<String, &crate::Class> = after
+        .packages
+        .values()
+        .flat_map(|package| package.classes.iter())
+        .map(|(key, class)| (after.class_path(key), class))
+        .collect();
+    let mut newly_uncovered_lines = vec![];
+    let mut functions_lost = vec![];
+    let mut before_classes: Vec<_> = before
+        .packages
+        .values()
+        .flat_map(|package| package.classes.iter())
+        .map(|(key, class)| (before.class_path(key), class))
+        .collect();
+    before_classes.sort_by(|(a, _), (b, _)| a.cmp(b));
+    for (file_name, class) in before_classes {
+        let Some(after_class) = after_classes.get(&file_name) else {
+            continue;
+        };
+        let mut lines: Vec<usize> = class
+            .lines
+            .iter()
+            .filter(|(number, line)| {
+                line.hits > 0
+                    && after_class
+                        .lines
+                        .get(number)
+                        .is_some_and(|after_line| after_line.hits == 0)
+            })
+            .map(|(number, _)| *number)
+            .collect();
+        if !lines.is_empty() {
+            lines.sort_unstable();
+            newly_uncovered_lines.push((file_name.clone(), lines));
+        }
+        for (method_name, (_, hits)) in &class.methods {
+            if *hits > 0
+                && after_class
+                    .methods
+                    .get(method_name)
+                    .is_some_and(|(_, after_hits)| *after_hits == 0)
+            {
+                functions_lost.push((file_name.clone(), method_name.clone()));
+            }
+        }
+    }
+
+    Comparison {
+        total: SummaryDelta {
+            name: "total".to_string(),
+            before: Some(before.summary()),
+            after: Some(after.summary()),
+        },
+        packages: deltas(package_summaries(before), package_summaries(after)),
+        files: deltas(file_summaries(before), file_summaries(after)),
+        newly_uncovered_lines,
+        functions_lost,
+    }
+}
+
+fn rate(summary: Option<&Summary>, metric: Metric) -> String {
+    summary.map_or_else(
+        || "-".to_string(),
+        |s| format!("{:.2}%", s.rate(metric) * 100.),
+    )
+}
+
+fn signed(delta: Option<f64>) -> String {
+    delta.map_or_else(|| "-".to_string(), |d| format!("{d:+.2}"))
+}
+
+fn json_str(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{:04x}", u32::from(c));
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn json_summary(summary: Option<&Summary>) -> String {
+    summary.map_or_else(
+        || "null".to_string(),
+        |s| {
+            format!(
+                "{{\"lines_covered\": {}, \"lines_total\": {}, \"line_rate\": {:.4}, \"branches_covered\": {}, \"branches_total\": {}, \"branch_rate\": {:.4}}}",
+                s.lines_covered,
+                s.lines_total,
+                s.line_rate(),
+                s.branches_covered,
+                s.branches_total,
+                s.branch_rate()
+            )
+        },
+    )
+}
+
+fn json_delta(delta: &SummaryDelta) -> String {
+    let number = |d: Option<f64>| d.map_or_else(|| "null".to_string(), |d| format!("{d:.2}"));
+    format!(
+        "{{\"name\": {}, \"before\": {}, \"after\": {}, \"line_delta\": {}, \"branch_delta\": {}}}",
+        json_str(&delta.name),
+        json_summary(delta.before.as_ref()),
+        json_summary(delta.after.as_ref()),
+        number(delta.delta(Metric::Lines)),
+        number(delta.delta(Metric::Branches)),
+    )
+}
+
+fn json_list<T, F: Fn(&T) -> String>(items: &[T], f: F) -> String {
+    format!("[{}]", items.iter().map(f).collect::<Vec<_>>().join(", "))
+}
+
+impl Comparison {
+    /// files only present in the new report
+    pub fn added_files(&self) -> impl Iterator<Item = &str> {
+        self.files
+            .iter()
+            .filter(|f| f.before.is_none())
+            .map(|f| f.name.as_str())
+    }
+
+    /// files only present in the old report
+    pub fn removed_files(&self) -> impl Iterator<Item = &str> {
+        self.files
+            .iter()
+            .filter(|f| f.after.is_none())
+            .map(|f| f.name.as_str())
+    }
+
+    /// largest drop of the total line or branch rate in percentage points, 0 if none dropped
+    #[must_use]
+    pub fn regression(&self) -> f64 {
+        [Metric::Lines, Metric::Branches]
+            .into_iter()
+            .filter_map(|metric| self.total.delta(metric))
+            .fold(0., |worst: f64, delta| worst.max(-delta))
+    }
+
+    /// Renders the total, all changed packages and files, added and removed files, newly
+    /// uncovered lines and functions that lost coverage.
+    #[must_use]
+    pub fn render(&self, format: CompareFormat) -> String {
+        let packages: Vec<&SummaryDelta> = self.packages.iter().filter(|d| d.changed()).collect();
+        let files: Vec<&SummaryDelta> = self
+            .files
+            .iter()
+            .filter(|d| d.changed() && d.before.is_some() && d.after.is_some())
+            .collect();
+        let added: Vec<&str> = self.added_files().collect();
+        let removed: Vec<&str> = self.removed_files().collect();
+        match format {
+            CompareFormat::Text => self.render_text(&packages, &files, &added, &removed),
+            CompareFormat::Markdown => self.render_markdown(&packages, &files, &added, &removed),
+            CompareFormat::Json => format!(
+                "{{\"total\": {}, \"packages\": {}, \"files\": {}, \"added_files\": {}, \"removed_files\": {}, \"newly_uncovered_lines\": {}, \"functions_lost\": {}}}\n",
+                json_delta(&self.total),
+                json_list(&packages, |d| json_delta(d)),
+                json_list(&files, |d| json_delta(d)),
+                json_list(&added, |f| json_str(f)),
+                json_list(&removed, |f| json_str(f)),
+                json_list(&self.newly_uncovered_lines, |(file, lines)| format!(
+                    "{{\"file\": {}, \"lines\": {}}}",
+                    json_str(file),
+                    json_list(lines, ToString::to_string)
+                )),
+                json_list(&self.functions_lost, |(file, function)| format!(
+                    "{{\"file\": {}, \"function\": {}}}",
+                    json_str(file),
+                    json_str(function)
+                )),
+            ),
+        }
+    }
+
+    fn render_text(
+        &self,
+        packages: &[&SummaryDelta],
+        files: &[&SummaryDelta],
+        added: &[&str],
+        removed: &[&str],
+    ) -> String {
+        let line = |d: &SummaryDelta| {
+            format!(
+                "{}: lines {} -> {} ({}), branches {} -> {} ({})",
+                d.name,
+                rate(d.before.as_ref(), Metric::Lines),
+                rate(d.after.as_ref(), Metric::Lines),
+                signed(d.delta(Metric::Lines)),
+                rate(d.before.as_ref(), Metric::Branches),
+                rate(d.after.as_ref(), Metric::Branches),
+                signed(d.delta(Metric::Branches)),
+            )
+        };
+        let mut out = line(&self.total).replacen("total", "Total", 1);
+        out.push('\n');
+        let mut section = |title: &str, entries: Vec<String>| {
+            if !entries.is_empty() {
+                let _ = writeln!(out, "\n{title}:");
+                for entry in entries {
+                    let _ = writeln!(out, "  {entry}");
+                }
+            }
+        };
+        section(
+            "Changed packages",
+            packages.iter().map(|d| line(d)).collect(),
+        );
+        section("Changed files", files.iter().map(|d| line(d)).collect());
+        section(
+            "Added files",
+            added.iter().map(ToString::to_string).collect(),
+        );
+        section(
+            "Removed files",
+            removed.iter().map(ToString::to_string).collect(),
+        );
+        section(
+            "Newly uncovered lines",
+            self.newly_uncovered_lines
+                .iter()
+                .map(|(file, lines)| format!("{file}: {}", line_ranges(lines)))
+                .collect(),
+        );
+        section(
+            "Functions that lost coverage",
+            self.functions_lost
+                .iter()
+                .map(|(file, function)| format!("{file}: {function}"))
+                .collect(),
+        );
+        out
+    }
+
+    fn render_markdown(
+        &self,
+        packages: &[&SummaryDelta],
+        files: &[&SummaryDelta],
+        added: &[&str],
+        removed: &[&str],
+    ) -> String {
+        let row = |name: &str, d: &SummaryDelta| {
+            format!(
+                "| {} | {} | {} | {} | {} | {} | {} |\n",
+                name.replace('|', "\\|"),
+                rate(d.before.as_ref(), Metric::Lines),
+                rate(d.after.as_ref(), Metric::Lines),
+                signed(d.delta(Metric::Lines)),
+                rate(d.before.as_ref(), Metric::Branches),
+                rate(d.after.as_ref(), Metric::Branches),
+                signed(d.delta(Metric::Branches)),
+            )
+        };
+        let head = "| Name | Lines before | Lines after | Δ | Branches before | Branches after | Δ |\n| :--- | ---: | ---: | ---: | ---: | ---: | ---: |\n";
+        let mut out = String::from("### Coverage comparison\n\n");
+        out.push_str(head);
+        out.push_str(&row("**Total**", &self.total));
+        let mut table = |title: &str, entries: &[&SummaryDelta]| {
+            if !entries.is_empty() {
+                let _ = write!(out, "\n#### {title}\n\n{head}");
+                for d in entries {
+                    out.push_str(&row(&d.name, d));
+                }
+            }
+        };
+        table("Changed packages", packages);
+        table("Changed files", files);
+        let mut list = |title: &str, entries: Vec<String>| {
+            if !entries.is_empty() {
+                let _ = writeln!(out, "\n#### {title}\n");
+                for entry in entries {
+                    let _ = writeln!(out, "- {entry}");
+                }
+            }
+        };
+        list(
+            "Added files",
+            added.iter().map(|f| format!("`{f}`")).collect(),
+        );
+        list(
+            "Removed files",
+            removed.iter().map(|f| format!("`{f}`")).collect(),
+        );
+        list(
+            "Newly uncovered lines",
+            self.newly_uncovered_lines
+                .iter()
+                .map(|(file, lines)| format!("`{file}`: {}", line_ranges(lines)))
+                .collect(),
+        );
+        list(
+            "Functions that lost coverage",
+            self.functions_lost
+                .iter()
+                .map(|(file, function)| format!("`{file}`: `{function}`"))
+                .collect(),
+        );
+        out
+    }
+}
@@ -2,32 +2,101 @@
 use regex::Regex;
 use rustc_demangle::demangle;
 use std::borrow::Cow;
-use std::io::{self, BufRead, BufReader, Write};
-use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::collections::{HashMap, HashSet};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 
-/// Basic interface to demangle function/method names
-pub trait Demangler<'a, 'b> {
+/// Basic interface to demangle function/method names, object safe, so the demangler can be
+/// chosen at runtime as `Box<dyn Demangler>`, see [`DemanglerKind`]
+pub trait Demangler {
     /// Demangle an identifier
     /// # Errors
     /// IO Errors
-    fn demangle(&'b mut self, ident: &'a str) -> io::Result<Cow<'a, str>>;
-    /// Consumes the instance closing opened resources
+    fn demangle<'a>(&mut self, ident: &'a str) -> io::Result<Cow<'a, str>>;
+    /// Announces identifiers that will be demangled, so demanglers talking to another process
+    /// can send them at once. Does nothing by default.
     /// # Errors
     /// IO Errors
-    fn stop(self) -> io::Result<()>;
+    fn prepare(&mut self, idents: &[&str]) -> io::Result<()> {
+        let _ = idents;
+        Ok(())
+    }
+    /// Closes opened resources like spawned processes, their failures are kept for
+    /// [`Demangler::take_diagnostics`]. Dropping a demangler closes them too, but silently.
+    /// Does nothing by default.
+    /// # Errors
+    /// IO Errors
+    fn stop(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+    /// Takes the warnings collected so far, e.g. failures of external tools and what they
+    /// printed to standard error, complete after [`Demangler::stop`]. None by default.
+    fn take_diagnostics(&mut self) -> Vec<String> {
+        vec![]
+    }
 }
 
-/// C++ demangling, actually accepts any demangler tool that works over standard input/standard output.
-/// It writes the mangled named to spawned process' standard input and reads the demangled response from
-/// standard output
+impl<D: Demangler + ?Sized> Demangler for Box<D> {
+    fn demangle<'a>(&mut self, ident: &'a str) -> io::Result<Cow<'a, str>> {
+        (**self).demangle(ident)
+    }
+
+    fn prepare(&mut self, idents: &[&str]) -> io::Result<()> {
+        (**self).prepare(idents)
+    }
+
+    fn stop(&mut self) -> io::Result<()> {
+        (**self).stop()
+    }
+
+    fn take_diagnostics(&mut self) -> Vec<String> {
+        (**self).take_diagnostics()
+    }
+}
+
+/// C++ demangling, actually accepts any demangler tool that works over standard input/standard output
+/// like `c++filt`: it reads one name per line and answers with one line each, echoing names it
+/// doesn't know.
+///
+/// Names are sent in batches, see [`Demangler::prepare`], followed by a sentinel line the tool
+/// echoes, so a batch is complete once the sentinel is read back. If the tool doesn't answer
+/// within the timeout, crashes or answers with a different number of lines, the names of the
+/// batch keep their mangled form. After a timeout or crash the tool is not used anymore. Such
+/// failures and anything the tool prints to standard error are returned by
+/// [`Demangler::take_diagnostics`]. Only the answers since the latest [`Demangler::prepare`] are
+/// kept, wrap it in a [`CachingDemangler`] to remember names.
 pub struct CppDemangler {
+    cmd: String,
     child: Child,
-    child_in: ChildStdin,
-    child_out: BufReader<ChildStdout>,
+    child_in: Option<ChildStdin>,
+    /// lines of the standard output, read by a thread so reading can time out
+    child_out: Receiver<String>,
+    /// collects the standard error until the tool exits
+    child_err: Option<JoinHandle<String>>,
+    timeout: Duration,
+    demangled: HashMap<String, String>,
+    batches: usize,
+    /// the tool timed out or exited, all further names stay mangled
+    failed: bool,
+    diagnostics: Vec<String>,
+    reaped: bool,
 }
 
+/// Sentinel line ending a batch, numbered so a late answer of an earlier batch can't match
+const SENTINEL: &str = "lcov2cobertura-end-of-batch-";
+
 impl CppDemangler {
-    /// Pass in full path to command that does the demangling.
+    /// Pass in full path to command that does the demangling, a batch times out after 10 seconds.
+    /// # Errors
+    /// IO Errors
+    pub fn new(cmd: &str) -> io::Result<Self> {
+        Self::with_args(cmd, &[])
+    }
+
+    /// Like [`CppDemangler::new`], but passes `args` to the command
     /// # Errors
     /// IO Errors
     /// # Panics
@@ -35,35 +104,268 @@ impl CppDemangler {
     #[allow(
         clippy::unwrap_used,
         clippy::unwrap_in_result,
-        reason = "stdin/stdout is only taken once, panic unlikely"
+        reason = "stdin/stdout/stderr is only taken once, panic unlikely"
     )]
-    pub fn new(cmd: &str) -> io::Result<Self> {
+    pub fn with_args(cmd: &str, args: &[&str]) -> io::Result<Self> {
         let mut child = Command::new(cmd)
+            .args(args)
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
             .spawn()?;
         let child_in = child.stdin.take().unwrap();
-        let child_out = BufReader::new(child.stdout.take().unwrap());
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        let mut stderr = child.stderr.take().unwrap();
+        let (sender, child_out) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in stdout.lines() {
+                let Ok(line) = line else { break };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        let child_err = std::thread::spawn(move || {
+            let mut buffer = String::new();
+            // a partially read standard error is still worth reporting
+            let _ = stderr.read_to_string(&mut buffer);
+            buffer
+        });
         Ok(Self {
+            cmd: cmd.to_string(),
             child,
-            child_in,
+            child_in: Some(child_in),
             child_out,
+            child_err: Some(child_err),
+            timeout: Duration::from_secs(10),
+            demangled: HashMap::new(),
+            batches: 0,
+            failed: false,
+            diagnostics: vec![],
+            reaped: false,
         })
     }
+
+    /// time the tool has to answer a batch and to exit after the last one
+    #[must_use]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// failures of the tool so far, including its standard error once it exited
+    #[must_use]
+    pub fn diagnostics(&self) -> &[String] {
+        &self.diagnostics
+    }
+
+    /// Sends `idents` and the sentinel, stores the answers if their number matches
+    fn batch(&mut self, idents: Vec<String>) {
+        if self.failed || idents.is_empty() {
+            return;
+        }
+        let Some(mut child_in) = self.child_in.take() else {
+            return;
+        };
+        self.batches += 1;
+        let sentinel = format!("{SENTINEL}{}", self.batches);
+        let mut input = idents.join("\n");
+        input.push('\n');
+        input.push_str(&sentinel);
+        input.push('\n');
+        // written by a thread, a tool not reading its input can't block us past the timeout
+        let writer = std::thread::spawn(move || {
+            child_in.write_all(input.as_bytes())?;
+            child_in.flush()?;
+            Ok::<_, io::Error>(child_in)
+        });
+
+        let deadline = Instant::now() + self.timeout;
+        let mut answers = Vec::with_capacity(idents.len());
+        let outcome = loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match self.child_out.recv_timeout(remaining) {
+                Ok(line) if line.trim() == sentinel => break Ok(()),
+                Ok(line) => answers.push(line.trim().to_string()),
+                Err(RecvTimeoutError::Timeout) => {
+                    break Err(format!("no answer within {:?}", self.timeout))
+                }
+                Err(RecvTimeoutError::Disconnected) => break Err("exited".to_string()),
+            }
+        };
+        if outcome.is_err() {
+            // unblocks the writer thread
+            let _ = self.child.kill();
+        }
+        if let Ok(Ok(child_in)) = writer.join() {
+            self.child_in = Some(child_in);
+        }
+        match outcome {
+            Ok(()) if answers.len() == idents.len() => {
+                self.demangled.extend(idents.into_iter().zip(answers));
+            }
+            Ok(()) => {
+                self.diagnostics.push(format!(
+                    "{} answered {} lines for {} names, keeping {}",
+                    self.cmd,
+                    answers.len(),
+                    idents.len(),
+                    if idents.len() == 1 {
+                        "it mangled"
+                    } else {
+                        "them mangled until they are retried one by one"
+                    }
+                ));
+                if let [ident] = idents.as_slice() {
+                    self.demangled.insert(ident.clone(), ident.clone());
+                }
+            }
+            Err(reason) => {
+                self.failed = true;
+                let status = self.reap();
+                let stderr = self.stderr();
+                self.diagnostics.push(format!(
+                    "{} {reason} ({status}), keeping {} and all further names mangled{stderr}",
+                    self.cmd,
+                    idents.len(),
+                ));
+            }
+        }
+    }
+
+    /// waits for the tool to exit, kills it after the timeout
+    fn reap(&mut self) -> String {
+        if self.reaped {
+            return "exited".to_string();
+        }
+        let deadline = Instant::now() + self.timeout;
+        let status = loop {
+            match self.child.try_wait() {
+                Ok(Some(status)) => break Ok(status),
+                Ok(None) if Instant::now() < deadline => {
+                    std::thread::sleep(Duration::from_millis(10));
+                }
+                Ok(None) => {
+                    let _ = self.child.kill();
+                    break self.child.wait();
+                }
+                Err(e) => break Err(e),
+            }
+        };
+        self.reaped = true;
+        match status {
+            Ok(status) => status.to_string(),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    /// standard error of the exited tool, formatted to be appended to a diagnostic
+    fn stderr(&mut self) -> String {
+        match self.child_err.take().map(JoinHandle::join) {
+            Some(Ok(stderr)) if !stderr.trim().is_empty() => {
+                format!(", standard error:\n{}", stderr.trim_end())
+            }
+            _ => String::new(),
+        }
+    }
 }
 
-impl<'a> Demangler<'a, '_> for CppDemangler {
-    fn demangle(&mut self, ident: &str) -> io::Result<Cow<'a, str>> {
-        self.child_in.write_all(format!("{ident}\n").as_bytes())?;
-        let mut line = String::new();
-        self.child_out.read_line(&mut line)?;
-        Ok(Cow::Owned(line.trim().into()))
+impl Demangler for CppDemangler {
+    fn demangle<'a>(&mut self, ident: &'a str) -> io::Result<Cow<'a, str>> {
+        if !self.demangled.contains_key(ident) {
+            self.batch(vec![ident.to_string()]);
+        }
+        Ok(self
+            .demangled
+            .get(ident)
+            .map_or(Cow::Borrowed(ident), |name| Cow::Owned(name.clone())))
     }
 
-    fn stop(mut self) -> io::Result<()> {
-        self.child.kill()?;
+    fn prepare(&mut self, idents: &[&str]) -> io::Result<()> {
+        // answers of earlier batches were used, dropping them keeps memory bounded
+        let announced: HashSet<&str> = idents.iter().copied().collect();
+        self.demangled
+            .retain(|ident, _| announced.contains(ident.as_str()));
+        let mut unknown: Vec<String> = idents
+            .iter()
+            .filter(|ident| !self.demangled.contains_key(**ident))
+            .map(|ident| (*ident).to_string())
+            .collect();
+        unknown.sort_unstable();
+        unknown.dedup();
+        self.batch(unknown);
         Ok(())
     }
+
+    fn stop(&mut self) -> io::Result<()> {
+        // closing the standard input lets the tool exit on its own
+        self.child_in = None;
+        if !self.reaped {
+            self.failed = true;
+            let status = self.reap();
+            let stderr = self.stderr();
+            if !stderr.is_empty() {
+                self.diagnostics
+                    .push(format!("{} exited ({status}){stderr}", self.cmd));
+            }
+        }
+        Ok(())
+    }
+
+    fn take_diagnostics(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.diagnostics)
+    }
+}
+
+impl Drop for CppDemangler {
+    fn drop(&mut self) {
+        if !self.reaped {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
+
+/// In-process Itanium C++ ABI demangler for GCC and Clang symbols, uses the
+/// [cpp_demangle](https://docs.rs/cpp_demangle/) crate. Identifiers that are no valid mangled
+/// names are returned unchanged, like `c++filt` does.
+#[cfg(feature = "cpp-demangle")]
+#[derive(Default)]
+pub struct ItaniumDemangler {
+    options: cpp_demangle::DemangleOptions,
+}
+
+#[cfg(feature = "cpp-demangle")]
+impl ItaniumDemangler {
+    /// demangles to the full signature like `c++filt`
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// omits the function parameters, `ns::f(int)` becomes `ns::f`
+    #[must_use]
+    pub fn no_params(mut self) -> Self {
+        self.options = self.options.no_params();
+        self
+    }
+
+    /// omits the return types of template functions
+    #[must_use]
+    pub fn no_return_type(mut self) -> Self {
+        self.options = self.options.no_return_type();
+        self
+    }
+}
+
+#[cfg(feature = "cpp-demangle")]
+impl Demangler for ItaniumDemangler {
+    fn demangle<'a>(&mut self, ident: &'a str) -> io::Result<Cow<'a, str>> {
+        Ok(cpp_demangle::Symbol::new(ident)
+            .ok()
+            .and_then(|symbol| symbol.demangle_with_options(&self.options).ok())
+            .map_or(Cow::Borrowed(ident), Cow::Owned))
+    }
 }
 
 /// Demangles rustc names, uses [rustc_demangle](https://docs.rs/rustc-demangle/) crate
@@ -92,17 +394,444 @@ impl RustDemangler {
         }
     }
 }
-impl<'a> Demangler<'a, '_> for RustDemangler {
-    fn demangle(&mut self, ident: &str) -> io::Result<Cow<'a, str>> {
+impl Demangler for RustDemangler {
+    fn demangle<'a>(&mut self, ident: &'a str) -> io::Result<Cow<'a, str>> {
         let demangled = demangle(ident).to_string();
         Ok(Cow::Owned(
             self.disambiguator.replace_all(&demangled, "::").to_string(),
         ))
     }
+}
+
+/// External tool speaking the protocol of [`CppDemangler`], started with the first name it gets.
+/// If it can't be started, e.g. because it isn't installed, the names keep their mangled form
+/// and the failure is a diagnostic.
+struct LazyTool {
+    cmd: String,
+    args: Vec<String>,
+    timeout: Duration,
+    /// kind of the names sent to the tool, for the diagnostic
+    names: &'static str,
+    tool: Option<Box<CppDemangler>>,
+    /// starting the tool failed, it isn't tried again
+    unavailable: bool,
+    diagnostics: Vec<String>,
+}
+
+impl LazyTool {
+    fn new(cmd: &str, names: &'static str) -> Self {
+        Self {
+            cmd: cmd.to_string(),
+            args: vec![],
+            timeout: Duration::from_secs(10),
+            names,
+            tool: None,
+            unavailable: false,
+            diagnostics: vec![],
+        }
+    }
+
+    /// the running tool, `None` if it can't be started
+    fn tool(&mut self) -> Option<&mut CppDemangler> {
+        if self.tool.is_none() && !self.unavailable {
+            let args: Vec<&str> = self.args.iter().map(String::as_str).collect();
+            match CppDemangler::with_args(&self.cmd, &args) {
+                Ok(tool) => self.tool = Some(Box::new(tool.timeout(self.timeout))),
+                Err(e) => {
+                    self.unavailable = true;
+                    self.diagnostics.push(format!(
+                        "cannot start {}: {e}, keeping {} names mangled",
+                        self.cmd, self.names
+                    ));
+                }
+            }
+        }
+        self.tool.as_deref_mut()
+    }
+}
+
+impl Demangler for LazyTool {
+    fn demangle<'a>(&mut self, ident: &'a str) -> io::Result<Cow<'a, str>> {
+        match self.tool() {
+            Some(tool) => tool.demangle(ident),
+            None => Ok(Cow::Borrowed(ident)),
+        }
+    }
+
+    fn prepare(&mut self, idents: &[&str]) -> io::Result<()> {
+        if idents.is_empty() {
+            return Ok(());
+        }
+        match self.tool() {
+            Some(tool) => tool.prepare(idents),
+            None => Ok(()),
+        }
+    }
+
+    fn stop(&mut self) -> io::Result<()> {
+        match &mut self.tool {
+            Some(tool) => tool.stop(),
+            None => Ok(()),
+        }
+    }
+
+    fn take_diagnostics(&mut self) -> Vec<String> {
+        let mut diagnostics = std::mem::take(&mut self.diagnostics);
+        if let Some(tool) = &mut self.tool {
+            diagnostics.extend(tool.take_diagnostics());
+        }
+        diagnostics
+    }
+}
+
+/// Demangles Swift symbols with the `swift-demangle` tool of the Swift toolchain, over the
+/// protocol of [`CppDemangler`]. Only Swift symbols are sent, `swift-demangle` would otherwise
+/// look for Swift names inside of any other name, everything else is kept as it is. The tool is
+/// started with the first Swift symbol, so reports without them don't need it installed. If it
+/// can't be started, Swift names are kept as they are and a diagnostic tells why.
+pub struct SwiftDemangler {
+    tool: LazyTool,
+}
+
+impl Default for SwiftDemangler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SwiftDemangler {
+    /// uses `swift-demangle` from the `PATH`
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_tool("swift-demangle")
+    }
+
+    /// uses the given `swift-demangle` executable
+    #[must_use]
+    pub fn with_tool(cmd: &str) -> Self {
+        Self {
+            tool: LazyTool::new(cmd, "Swift"),
+        }
+    }
+
+    /// short names without module and parameter types, `main.Foo.bar(Swift.Int) -> ()` becomes
+    /// `Foo.bar(_:)`
+    #[must_use]
+    pub fn simplified(mut self) -> Self {
+        self.tool.args = vec!["--simplified".to_string()];
+        self
+    }
+
+    /// time the tool has to answer a batch, see [`CppDemangler::timeout`]
+    #[must_use]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.tool.timeout = timeout;
+        self
+    }
+}
+
+impl Demangler for SwiftDemangler {
+    fn demangle<'a>(&mut self, ident: &'a str) -> io::Result<Cow<'a, str>> {
+        if SymbolKind::detect(ident) != SymbolKind::Swift {
+            return Ok(Cow::Borrowed(ident));
+        }
+        self.tool.demangle(ident)
+    }
+
+    fn prepare(&mut self, idents: &[&str]) -> io::Result<()> {
+        let swift: Vec<&str> = idents
+            .iter()
+            .copied()
+            .filter(|ident| SymbolKind::detect(ident) == SymbolKind::Swift)
+            .collect();
+        self.tool.prepare(&swift)
+    }
+
+    fn stop(&mut self) -> io::Result<()> {
+        self.tool.stop()
+    }
+
+    fn take_diagnostics(&mut self) -> Vec<String> {
+        self.tool.take_diagnostics()
+    }
+}
+
+/// In-process demangler for `_D` symbols of the D compilers, prints them like `core.demangle`,
+/// e.g. `_D4test3fooFiZv` becomes `void test.foo(int)`. Identifiers that are no valid mangled
+/// names are returned unchanged.
+#[derive(Default)]
+pub struct DDemangler {}
+
+impl DDemangler {
+    /// constructs the `DDemangler`
+    #[must_use]
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Demangler for DDemangler {
+    fn demangle<'a>(&mut self, ident: &'a str) -> io::Result<Cow<'a, str>> {
+        Ok(crate::dlang::demangle(ident).map_or(Cow::Borrowed(ident), Cow::Owned))
+    }
+}
+
+/// Memoizes the names of another demangler, so every unique symbol is demangled once no matter
+/// in how many classes it appears. [`Demangler::prepare`] demangles all announced names up front.
+pub struct CachingDemangler<D> {
+    inner: D,
+    names: HashMap<String, String>,
+    /// names of the previous generation of a bounded cache, dropped once `names` fills up again
+    older: HashMap<String, String>,
+    capacity: Option<usize>,
+}
+
+impl<D: Demangler> CachingDemangler<D> {
+    /// wraps `inner` with an empty cache
+    #[must_use]
+    pub fn new(inner: D) -> Self {
+        Self::with_names(inner, HashMap::new())
+    }
+
+    /// wraps `inner` with already demangled names, e.g. from a parallel pre-pass
+    #[must_use]
+    pub fn with_names(inner: D, names: HashMap<String, String>) -> Self {
+        Self {
+            inner,
+            names,
+            older: HashMap::new(),
+            capacity: None,
+        }
+    }
 
-    fn stop(self) -> io::Result<()> {
+    /// wraps `inner` with a cache keeping between `capacity` and twice as many recently used
+    /// names, e.g. for streaming, where memory must not grow with the report. Once `capacity`
+    /// names are cached they become the previous generation and the generation before is
+    /// dropped, names used again are moved to the current one.
+    #[must_use]
+    pub fn bounded(inner: D, capacity: usize) -> Self {
+        Self {
+            capacity: Some(capacity.max(1)),
+            ..Self::new(inner)
+        }
+    }
+}
+
+impl<D: Demangler> Demangler for CachingDemangler<D> {
+    fn demangle<'a>(&mut self, ident: &'a str) -> io::Result<Cow<'a, str>> {
+        if !self.names.contains_key(ident) {
+            let name = match self.older.remove(ident) {
+                Some(name) => name,
+                None => self.inner.demangle(ident)?.into_owned(),
+            };
+            if self
+                .capacity
+                .is_some_and(|capacity| self.names.len() >= capacity)
+            {
+                self.older = std::mem::take(&mut self.names);
+            }
+            self.names.insert(ident.to_string(), name);
+        }
+        let name = &self.names[ident];
+        Ok(if name == ident {
+            Cow::Borrowed(ident)
+        } else {
+            Cow::Owned(name.clone())
+        })
+    }
+
+    fn prepare(&mut self, idents: &[&str]) -> io::Result<()> {
+        let unknown: Vec<&str> = idents
+            .iter()
+            .copied()
+            .filter(|ident| !self.names.contains_key(*ident) && !self.older.contains_key(*ident))
+            .collect();
+        self.inner.prepare(&unknown)?;
+        for ident in unknown {
+            self.demangle(ident)?;
+        }
         Ok(())
     }
+
+    fn stop(&mut self) -> io::Result<()> {
+        self.inner.stop()
+    }
+
+    fn take_diagnostics(&mut self) -> Vec<String> {
+        self.inner.take_diagnostics()
+    }
+}
+
+/// Mangling scheme of a symbol, detected by [`SymbolKind::detect`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    /// legacy `_ZN...17h<hash>E` or v0 `_R` Rust symbols
+    Rust,
+    /// Itanium ABI `_Z` symbols of GCC and Clang, `__Z` on macOS
+    Cpp,
+    /// `$s`, `$S` or `_T0` Swift symbols
+    Swift,
+    /// `_D` symbols of the D compilers
+    D,
+    /// anything else, e.g. C functions or JavaScript names
+    Unknown,
+}
+
+impl SymbolKind {
+    /// Detects the mangling scheme after the prefix of a symbol. Legacy Rust symbols start with
+    /// `_ZN` like C++ ones, so Rust symbols have to be fully demangled by `rustc_demangle`.
+    #[must_use]
+    pub fn detect(ident: &str) -> Self {
+        if rustc_demangle::try_demangle(ident).is_ok() {
+            Self::Rust
+        } else if ident.starts_with("_Z") || ident.starts_with("__Z") {
+            Self::Cpp
+        } else if ["$s", "$S", "_$s", "_$S", "_T0"]
+            .iter()
+            .any(|prefix| ident.starts_with(prefix))
+        {
+            Self::Swift
+        } else if ident == "_Dmain"
+            || ident
+                .strip_prefix("_D")
+                .is_some_and(|rest| rest.starts_with(|c: char| c.is_ascii_digit()))
+        {
+            Self::D
+        } else {
+            Self::Unknown
+        }
+    }
+}
+
+/// C++ part of the [`AutoDemangler`]
+enum CppBackend {
+    #[cfg(feature = "cpp-demangle")]
+    Itanium(ItaniumDemangler),
+    /// external tool, only started once the first C++ symbol is seen
+    External(LazyTool),
+}
+
+/// Demangles reports of mixed Rust/C++/Swift/D projects, picks the demangler for every symbol by
+/// its [`SymbolKind`]. Unknown symbols are kept as they are.
+pub struct AutoDemangler {
+    rust: RustDemangler,
+    cpp: CppBackend,
+    swift: SwiftDemangler,
+    d: DDemangler,
+}
+
+impl Default for AutoDemangler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AutoDemangler {
+    /// C++ symbols are demangled with the [`ItaniumDemangler`] if the `cpp-demangle` feature
+    /// is enabled, with `c++filt` otherwise
+    #[must_use]
+    pub fn new() -> Self {
+        #[cfg(feature = "cpp-demangle")]
+        return Self::with_itanium(ItaniumDemangler::new());
+        #[cfg(not(feature = "cpp-demangle"))]
+        return Self::with_cpp_tool("c++filt");
+    }
+
+    /// C++ symbols are demangled with the given [`ItaniumDemangler`], e.g. without parameters
+    #[cfg(feature = "cpp-demangle")]
+    #[must_use]
+    pub fn with_itanium(itanium: ItaniumDemangler) -> Self {
+        Self {
+            rust: RustDemangler::new(),
+            cpp: CppBackend::Itanium(itanium),
+            swift: SwiftDemangler::new(),
+            d: DDemangler::new(),
+        }
+    }
+
+    /// C++ symbols are demangled by an external tool like [`CppDemangler`], it is started with
+    /// the first C++ symbol, so reports without C++ don't need it installed. If it can't be
+    /// started, C++ names are kept as they are and a diagnostic tells why.
+    #[must_use]
+    pub fn with_cpp_tool(cmd: &str) -> Self {
+        Self {
+            rust: RustDemangler::new(),
+            cpp: CppBackend::External(LazyTool::new(cmd, "C++")),
+            swift: SwiftDemangler::new(),
+            d: DDemangler::new(),
+        }
+    }
+
+    /// time the external C++ tool has to answer a batch, see [`CppDemangler::timeout`], the
+    /// [`SwiftDemangler`] has its own
+    #[must_use]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        match &mut self.cpp {
+            #[cfg(feature = "cpp-demangle")]
+            CppBackend::Itanium(_) => (),
+            CppBackend::External(tool) => tool.timeout = timeout,
+        }
+        self
+    }
+
+    /// Swift symbols are demangled by the given [`SwiftDemangler`] instead of a default one
+    #[must_use]
+    pub fn with_swift(mut self, swift: SwiftDemangler) -> Self {
+        self.swift = swift;
+        self
+    }
+}
+
+impl Demangler for AutoDemangler {
+    fn demangle<'a>(&mut self, ident: &'a str) -> io::Result<Cow<'a, str>> {
+        match SymbolKind::detect(ident) {
+            SymbolKind::Rust => self.rust.demangle(ident),
+            SymbolKind::Cpp => match &mut self.cpp {
+                #[cfg(feature = "cpp-demangle")]
+                CppBackend::Itanium(itanium) => itanium.demangle(ident),
+                CppBackend::External(tool) => tool.demangle(ident),
+            },
+            SymbolKind::Swift => self.swift.demangle(ident),
+            SymbolKind::D => self.d.demangle(ident),
+            SymbolKind::Unknown => Ok(Cow::Borrowed(ident)),
+        }
+    }
+
+    fn prepare(&mut self, idents: &[&str]) -> io::Result<()> {
+        self.swift.prepare(idents)?;
+        match &mut self.cpp {
+            // in-process, nothing gained by knowing the names early
+            #[cfg(feature = "cpp-demangle")]
+            CppBackend::Itanium(_) => Ok(()),
+            CppBackend::External(tool) => {
+                let cpp: Vec<&str> = idents
+                    .iter()
+                    .copied()
+                    .filter(|ident| SymbolKind::detect(ident) == SymbolKind::Cpp)
+                    .collect();
+                tool.prepare(&cpp)
+            }
+        }
+    }
+
+    fn stop(&mut self) -> io::Result<()> {
+        match &mut self.cpp {
+            #[cfg(feature = "cpp-demangle")]
+            CppBackend::Itanium(_) => (),
+            CppBackend::External(tool) => tool.stop()?,
+        }
+        self.swift.stop()
+    }
+
+    fn take_diagnostics(&mut self) -> Vec<String> {
+        let mut diagnostics = match &mut self.cpp {
+            #[cfg(feature = "cpp-demangle")]
+            CppBackend::Itanium(_) => vec![],
+            CppBackend::External(tool) => tool.take_diagnostics(),
+        };
+        diagnostics.extend(self.swift.take_diagnostics());
+        diagnostics
+    }
 }
 
 /// Default demangler, does nothing to the identifier names.
@@ -120,12 +849,124 @@ impl NullDemangler {
         Self {}
     }
 }
-impl<'a, 'b> Demangler<'a, 'b> for NullDemangler {
-    fn demangle(&'b mut self, ident: &'a str) -> io::Result<Cow<'a, str>> {
+impl Demangler for NullDemangler {
+    fn demangle<'a>(&mut self, ident: &'a str) -> io::Result<Cow<'a, str>> {
         Ok(Cow::Borrowed(ident))
     }
+}
 
-    fn stop(self) -> io::Result<()> {
-        Ok(())
+/// Settings of the demanglers built by [`DemanglerKind::build`]
+#[derive(Debug, Clone)]
+pub struct DemanglerOptions {
+    /// time an external tool has to answer a batch of names, see [`CppDemangler::timeout`]
+    pub timeout: Duration,
+    /// omit the function parameters of C++ names, see [`ItaniumDemangler::no_params`]
+    #[cfg(feature = "cpp-demangle")]
+    pub no_params: bool,
+    /// omit the return types of C++ template functions, see
+    /// [`ItaniumDemangler::no_return_type`]
+    #[cfg(feature = "cpp-demangle")]
+    pub no_return_type: bool,
+    /// short Swift names, see [`SwiftDemangler::simplified`]
+    pub swift_simplified: bool,
+}
+
+impl Default for DemanglerOptions {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(10),
+            #[cfg(feature = "cpp-demangle")]
+            no_params: false,
+            #[cfg(feature = "cpp-demangle")]
+            no_return_type: false,
+            swift_simplified: false,
+        }
+    }
+}
+
+/// Demangler chosen at runtime, e.g. on the command line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DemanglerKind {
+    /// keeps names as they are, [`NullDemangler`]
+    Null,
+    /// `$rust`, [`RustDemangler`]
+    Rust,
+    /// `$cpp`, [`ItaniumDemangler`]
+    #[cfg(feature = "cpp-demangle")]
+    Cpp,
+    /// `$swift`, [`SwiftDemangler`]
+    Swift,
+    /// `$d`, [`DDemangler`]
+    D,
+    /// `auto`, [`AutoDemangler`]
+    Auto,
+    /// path to an external tool like `c++filt`, [`CppDemangler`]
+    External(String),
+}
+
+impl std::str::FromStr for DemanglerKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "$null" => Ok(Self::Null),
+            "$rust" => Ok(Self::Rust),
+            #[cfg(feature = "cpp-demangle")]
+            "$cpp" => Ok(Self::Cpp),
+            #[cfg(not(feature = "cpp-demangle"))]
+            "$cpp" => {
+                anyhow::bail!("the internal C++ demangler needs the cargo feature `cpp-demangle`")
+            }
+            "$swift" => Ok(Self::Swift),
+            "$d" => Ok(Self::D),
+            "auto" => Ok(Self::Auto),
+            "" => anyhow::bail!("empty demangler"),
+            cmd => Ok(Self::External(cmd.to_string())),
+        }
+    }
+}
+
+impl DemanglerKind {
+    /// Creates a demangler of this kind, external tools are started right away, except for
+    /// `swift-demangle` and the C++ part of `auto`
+    /// # Errors
+    /// IO Errors starting an external tool
+    pub fn build(&self, options: &DemanglerOptions) -> io::Result<Box<dyn Demangler>> {
+        #[cfg(feature = "cpp-demangle")]
+        let itanium = || {
+            let mut itanium = ItaniumDemangler::new();
+            if options.no_params {
+                itanium = itanium.no_params();
+            }
+            if options.no_return_type {
+                itanium = itanium.no_return_type();
+            }
+            itanium
+        };
+        let swift = || {
+            let swift = SwiftDemangler::new().timeout(options.timeout);
+            if options.swift_simplified {
+                swift.simplified()
+            } else {
+                swift
+            }
+        };
+        Ok(match self {
+            Self::Null => Box::new(NullDemangler::new()),
+            Self::Rust => Box::new(RustDemangler::new()),
+            #[cfg(feature = "cpp-demangle")]
+            Self::Cpp => Box::new(itanium()),
+            Self::Swift => Box::new(swift()),
+            Self::D => Box::new(DDemangler::new()),
+            #[cfg(feature = "cpp-demangle")]
+            Self::Auto => Box::new(AutoDemangler::with_itanium(itanium()).with_swift(swift())),
+            #[cfg(not(feature = "cpp-demangle"))]
+            Self::Auto => Box::new(
+                AutoDemangler::new()
+                    .timeout(options.timeout)
+                    .with_swift(swift()),
+            ),
+            Self::External(cmd) => Box::new(CppDemangler::new(cmd)?.timeout(options.timeout)),
+        })
     }
 }
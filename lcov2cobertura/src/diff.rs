@@ -0,0 +1,284 @@
+//! Coverage of the lines changed in a unified diff, e.g. `git diff -U0 main...HEAD`
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write as _;
+
+use crate::{percent, Class, CoverageData};
+
+/// Added or modified line numbers of the new file version, keyed by file path
+pub type ChangedLines = BTreeMap<String, BTreeSet<usize>>;
+
+/// Parses `@@ -a,b +c,d @@` into the old and new line count and the new start line
+fn parse_hunk_header(line: &str) -> anyhow::Result<(usize, usize, usize)> {
+    let mut ranges = line
+        .trim_start_matches('@')
+        .split_whitespace()
+        .take_while(|r| !r.starts_with('@'));
+    let count = |range: Option<&str>, sign: char| -> anyhow::Result<(usize, usize)> {
+        let range = range
+            .and_then(|r| r.strip_prefix(sign))
+            .ok_or_else(|| anyhow::anyhow!("malformed hunk header {line:?}"))?;
+        let mut split = range.splitn(2, ',');
+        let start = split.next().unwrap_or_default().parse()?;
+        let count = split.next().map_or(Ok(1), str::parse)?;
+        Ok((start, count))
+    };
+    let (_, old_count) = count(ranges.next(), '-')?;
+    let (new_start, new_count) = count(ranges.next(), '+')?;
+    Ok((old_count, new_count, new_start))
+}
+
+/// Collects the added lines per file of a unified diff, deleted files are skipped and the
+/// `b/` prefix of git diffs is removed.
+///
+/// # Errors
+///
+/// Malformed hunk headers.
+pub fn parse_unified_diff(diff: &str) -> anyhow::Result<ChangedLines> {
+    let mut changed = ChangedLines::new();
+    let mut current: Option<String> = None;
+    let mut lines = diff.lines();
+    while let Some(line) = lines.next() {
+        if let Some(path) = line.strip_prefix("+++ ") {
+            // strip optional timestamp separated by a tab
+            let path = path.split('\t').next().unwrap_or_default();
+            current = if path == "/dev/null" {
+                None
+            } else {
+                Some(path.strip_prefix("b/").unwrap_or(path).to_string())
+            };
+        } else if line.starts_with("@@ ") {
+            let (mut old_remaining, mut new_remaining, mut new_line) = parse_hunk_header(line)?;
+            while old_remaining > 0 || new_remaining > 0 {
+                let Some(body) = lines.next() else {
+                    break;
+                };
+                match body.chars().next() {
+                    Some('+') => {
+                        if let Some(path) = &current {
+                            changed.entry(path.clone()).or_default().insert(new_line);
+                        }
+                        new_line += 1;
+                        new_remaining = new_remaining.saturating_sub(1);
+                    }
+                    Some('-') => old_remaining = old_remaining.saturating_sub(1),
+                    Some('\\') => (), // "\ No newline at end of file"
+                    _ => {
+                        // context line, an empty line is an empty context line
+                        new_line += 1;
+                        old_remaining = old_remaining.saturating_sub(1);
+                        new_remaining = new_remaining.saturating_sub(1);
+                    }
+                }
+            }
+        }
+    }
+    Ok(changed)
+}
+
+/// Coverage of the changed lines of one file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileDiffCoverage {
+    /// source file name relative to its source root, prefixed with the root if that is not the
+    /// base directory
+    pub file_name: String,
+    /// changed and instrumented lines hit at least once
+    pub covered: usize,
+    /// changed and instrumented lines
+    pub total: usize,
+    /// changed and instrumented lines never hit
+    pub uncovered_lines: Vec<usize>,
+}
+
+/// A file of the coverage data matching several changed files or a changed file matching
+/// several files of the coverage data, its changed lines are not counted
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AmbiguousMatch {
+    /// file name in the coverage data or path in the diff
+    pub path: String,
+    /// the paths of the other side it matches, sorted
+    pub candidates: Vec<String>,
+}
+
+/// Coverage of the changed lines of all files
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DiffCoverage {
+    /// files with at least one changed and instrumented line, sorted by name
+    pub files: Vec<FileDiffCoverage>,
+    /// paths that couldn't be told apart, sorted by path
+    pub ambiguous: Vec<AmbiguousMatch>,
+}
+
+impl DiffCoverage {
+    /// changed and instrumented lines hit at least once
+    #[must_use]
+    pub fn covered(&self) -> usize {
+        self.files.iter().map(|f| f.covered).sum()
+    }
+
+    /// changed and instrumented lines
+    #[must_use]
+    pub fn total(&self) -> usize {
+        self.files.iter().map(|f| f.total).sum()
+    }
+
+    /// ratio of covered changed lines, between 0 and 1
+    #[must_use]
+    pub fn rate(&self) -> f64 {
+        percent(self.total(), self.covered())
+    }
+
+    /// Human readable report listing the uncovered changed lines per file
+    #[must_use]
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for file in &self.files {
+            let _ = write!(
+                out,
+                "{}: {:.2}% ({}/{})",
+                file.file_name,
+                percent(file.total, file.covered) * 100.,
+                file.covered,
+                file.total
+            );
+            if !file.uncovered_lines.is_empty() {
+                let _ = write!(out, ", uncovered: {}", line_ranges(&file.uncovered_lines));
+            }
+            out.push('\n');
+        }
+        for ambiguous in &self.ambiguous {
+            let _ = writeln!(
+                out,
+                "{} is ambiguous, matches {}, not counted",
+                ambiguous.path,
+                ambiguous.candidates.join(", ")
+            );
+        }
+        if self.total() == 0 {
+            out.push_str("Diff coverage: no instrumented lines changed\n");
+        } else {
+            let _ = writeln!(
+                out,
+                "Diff coverage: {:.2}% ({}/{})",
+                self.rate() * 100.,
+                self.covered(),
+                self.total()
+            );
+        }
+        out
+    }
+}
+
+/// Formats sorted line numbers as `1-3, 7, 9-10`
+pub(crate) fn line_ranges(lines: &[usize]) -> String {
+    let mut ranges: Vec<(usize, usize)> = vec![];
+    for &line in lines {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == line => *end = line,
+            _ => ranges.push((line, line)),
+        }
+    }
+    ranges
+        .iter()
+        .map(|(start, end)| {
+            if start == end {
+                start.to_string()
+            } else {
+                format!("{start}-{end}")
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+/// Diff paths are relative to the repository root, class paths relative to `base_dir` or
+/// absolute, so they match when one is a suffix of the other on a path component boundary.
+fn same_file(diff_path: &str, file_name: &str) -> bool {
+    let (long, short) = if diff_path.len() >= file_name.len() {
+        (diff_path, file_name)
+    } else {
+        (file_name, diff_path)
+    };
+    long.strip_suffix(short)
+        .is_some_and(|rest| rest.is_empty() || rest.ends_with(['/', '\\']))
+}
+
+fn file_diff_coverage(file_name: &str, class: &Class, lines: &BTreeSet<usize>) -> FileDiffCoverage {
+    let mut result = FileDiffCoverage {
+        file_name: file_name.to_string(),
+        ..FileDiffCoverage::default()
+    };
+    for line_number in lines {
+        if let Some(line) = class.lines.get(line_number) {
+            result.total += 1;
+            if line.hits > 0 {
+                result.covered += 1;
+            } else {
+                result.uncovered_lines.push(*line_number);
+            }
+        }
+    }
+    result
+}
+
+/// Intersects the changed lines with the instrumented lines of the coverage data. Files are
+/// matched by their path including the source root, see [`same_file`], an equal path wins over
+/// suffix matches. Paths still matching several files of the other side are reported as
+/// ambiguous instead of picking one.
+#[must_use]
+pub fn diff_coverage(cov_data: &CoverageData, changed: &ChangedLines) -> DiffCoverage {
+    let classes: Vec<(String, &Class)> = cov_data
+        .packages
+        .values()
+        .flat_map(|package| package.classes.iter())
+        .map(|(key, class)| (cov_data.class_path(key), class))
+        .collect();
+    let mut ambiguous = vec![];
+    // classes matched by each diff path
+    let mut matches: BTreeMap<&str, Vec<&(String, &Class)>> = BTreeMap::new();
+    for entry @ (file_name, _) in &classes {
+        let candidates: Vec<&str> = changed
+            .keys()
+            .map(String::as_str)
+            .filter(|diff_path| same_file(diff_path, file_name))
+            .collect();
+        match candidates.as_slice() {
+            [] => (),
+            [diff_path] => matches.entry(diff_path).or_default().push(entry),
+            _ if changed.contains_key(file_name) => {
+                matches.entry(file_name).or_default().push(entry);
+            }
+            _ => ambiguous.push(AmbiguousMatch {
+                path: file_name.clone(),
+                candidates: candidates.iter().map(ToString::to_string).collect(),
+            }),
+        }
+    }
+    let mut files = vec![];
+    for (diff_path, mut entries) in matches {
+        if entries.len() > 1 {
+            if let Some(exact) = entries.iter().find(|(file_name, _)| file_name == diff_path) {
+                entries = vec![*exact];
+            } else {
+                let mut candidates: Vec<String> = entries
+                    .iter()
+                    .map(|(file_name, _)| file_name.clone())
+                    .collect();
+                candidates.sort_unstable();
+                ambiguous.push(AmbiguousMatch {
+                    path: diff_path.to_string(),
+                    candidates,
+                });
+                continue;
+            }
+        }
+        for (file_name, class) in entries {
+            let result = file_diff_coverage(file_name, class, &changed[diff_path]);
+            if result.total > 0 {
+                files.push(result);
+            }
+        }
+    }
+    files.sort_by(|a, b| a.file_name.cmp(&b.file_name));
+    ambiguous.sort_by(|a, b| a.path.cmp(&b.path));
+    DiffCoverage { files, ambiguous }
+}
@@ -0,0 +1,593 @@
+//! In-process demangling of D symbols following the
+//! [D ABI](https://dlang.org/spec/abi.html#name_mangling), output like `core.demangle`
+use std::fmt::Write as _;
+
+/// nesting of types and back references, deeper symbols are kept mangled
+const MAX_DEPTH: usize = 64;
+
+/// Demangles a D symbol like `_D4test3fooFiZv` to `void test.foo(int)`, `None` if it isn't a
+/// valid one
+pub(crate) fn demangle(symbol: &str) -> Option<String> {
+    if symbol == "_Dmain" {
+        return Some("D main".to_string());
+    }
+    if !symbol.starts_with("_D") {
+        return None;
+    }
+    let mut parser = Parser {
+        buf: symbol.as_bytes(),
+        pos: 2,
+        depth: 0,
+    };
+    let name = parser.mangled_name()?;
+    (parser.pos == parser.buf.len()).then_some(name)
+}
+
+/// Function type without the return type, of a function symbol or a type
+#[derive(Default)]
+struct Function {
+    /// attributes like `pure` or `@safe`
+    attributes: Vec<&'static str>,
+    /// parameter list with parentheses
+    parameters: String,
+    /// modifiers of `this` like `const`
+    this: Vec<&'static str>,
+}
+
+struct Parser<'a> {
+    buf: &'a [u8],
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.buf.get(self.pos).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<u8> {
+        self.buf.get(self.pos + offset).copied()
+    }
+
+    fn eat(&mut self, c: u8) -> bool {
+        let found = self.peek() == Some(c);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let c = self.peek()?;
+        self.pos += 1;
+        Some(c)
+    }
+
+    /// runs `parse` at `pos` and continues after the current position, for back references
+    fn at<T>(&mut self, pos: usize, parse: impl FnOnce(&mut Self) -> Option<T>) -> Option<T> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return None;
+        }
+        let resume = self.pos;
+        self.pos = pos;
+        let result = parse(self);
+        self.pos = resume;
+        self.depth -= 1;
+        result
+    }
+
+    fn number(&mut self) -> Option<usize> {
+        let start = self.pos;
+        let mut n = 0usize;
+        while let Some(c @ b'0'..=b'9') = self.peek() {
+            n = n.checked_mul(10)?.checked_add(usize::from(c - b'0'))?;
+            self.pos += 1;
+        }
+        (self.pos > start).then_some(n)
+    }
+
+    /// `Q` and a base 26 distance back from the `Q`, upper case letters are the leading digits
+    fn back_reference(&mut self) -> Option<usize> {
+        let start = self.pos;
+        if !self.eat(b'Q') {
+            return None;
+        }
+        let mut distance = 0usize;
+        loop {
+            let c = self.next()?;
+            let (digit, last) = match c {
+                b'A'..=b'Z' => (c - b'A', false),
+                b'a'..=b'z' => (c - b'a', true),
+                _ => return None,
+            };
+            distance = distance.checked_mul(26)?.checked_add(usize::from(digit))?;
+            if last {
+                break;
+            }
+        }
+        (distance > 0 && distance <= start).then(|| start - distance)
+    }
+
+    /// whether a symbol name follows, rather than a type
+    fn symbol_name_front(&mut self) -> bool {
+        match self.peek() {
+            Some(b'0'..=b'9' | b'_') => true,
+            Some(b'Q') => {
+                let start = self.pos;
+                let target = self.back_reference();
+                self.pos = start;
+                target.is_some_and(|target| self.buf[target].is_ascii_digit())
+            }
+            _ => false,
+        }
+    }
+
+    fn mangled_name(&mut self) -> Option<String> {
+        let (name, function) = self.qualified_name()?;
+        if self.pos == self.buf.len() || self.eat(b'Z') {
+            return Some(name);
+        }
+        let ty = self.ty()?;
+        Some(match function {
+            Some(function) => {
+                let mut demangled = String::new();
+                for attribute in function.attributes {
+                    demangled.push_str(attribute);
+                    demangled.push(' ');
+                }
+                let _ = write!(demangled, "{ty} {name}{}", function.parameters);
+                for modifier in function.this {
+                    demangled.push(' ');
+                    demangled.push_str(modifier);
+                }
+                demangled
+            }
+            None => format!("{ty} {name}"),
+        })
+    }
+
+    /// dotted name with the parameters of enclosing functions, the function type of the last
+    /// part is returned separately
+    fn qualified_name(&mut self) -> Option<(String, Option<Function>)> {
+        let mut name = String::new();
+        loop {
+            name.push_str(&self.symbol_name()?);
+            let function = self.function_no_return();
+            if !self.symbol_name_front() {
+                return Some((name, function));
+            }
+            if let Some(function) = function {
+                name.push_str(&function.parameters);
+            }
+            name.push('.');
+        }
+    }
+
+    /// qualified name with all function parameters, e.g. of a class type
+    fn full_qualified_name(&mut self) -> Option<String> {
+        let (mut name, function) = self.qualified_name()?;
+        if let Some(function) = function {
+            name.push_str(&function.parameters);
+        }
+        Some(name)
+    }
+
+    fn symbol_name(&mut self) -> Option<String> {
+        match self.peek()? {
+            b'_' => self.template_instance(),
+            b'0' => {
+                self.pos += 1;
+                Some("__anonymous".to_string())
+            }
+            _ => self.lname(),
+        }
+    }
+
+    /// length prefixed identifier or a back reference to one
+    fn lname(&mut self) -> Option<String> {
+        if self.peek() == Some(b'Q') {
+            let target = self.back_reference()?;
+            return self.at(target, Self::symbol_name);
+        }
+        let len = self.number()?;
+        let end = self.pos.checked_add(len)?;
+        let name = self.buf.get(self.pos..end)?;
+        if name.starts_with(b"__T") || name.starts_with(b"__U") {
+            // template instance of the old mangling, nested in the length
+            let instance = self.template_instance()?;
+            return (self.pos == end).then_some(instance);
+        }
+        let name = std::str::from_utf8(name).ok()?;
+        self.pos = end;
+        Some(name.to_string())
+    }
+
+    fn template_instance(&mut self) -> Option<String> {
+        if !(self.buf[self.pos..].starts_with(b"__T") || self.buf[self.pos..].starts_with(b"__U")) {
+            return None;
+        }
+        self.pos += 3;
+        let name = self.lname()?;
+        let mut arguments = vec![];
+        while !self.eat(b'Z') {
+            // specialization
+            self.eat(b'H');
+            arguments.push(match self.next()? {
+                b'T' => self.ty()?,
+                b'V' => {
+                    let ty = self.ty()?;
+                    self.value(&ty)?
+                }
+                b'S' => self.symbol_argument()?,
+                b'X' => {
+                    let len = self.number()?;
+                    let end = self.pos.checked_add(len)?;
+                    let mangled = std::str::from_utf8(self.buf.get(self.pos..end)?).ok()?;
+                    self.pos = end;
+                    mangled.to_string()
+                }
+                _ => return None,
+            });
+        }
+        Some(format!("{name}!({})", arguments.join(", ")))
+    }
+
+    /// alias template argument, a length prefixed mangled name or a qualified name
+    fn symbol_argument(&mut self) -> Option<String> {
+        let start = self.pos;
+        if let Some(len) = self.number() {
+            if self.buf[self.pos..].starts_with(b"_D") {
+                let end = self.pos.checked_add(len)?;
+                self.pos += 2;
+                let (name, _) = self.qualified_name()?;
+                // the type of the symbol is not shown
+                self.pos = end;
+                return Some(name);
+            }
+        }
+        self.pos = start;
+        self.full_qualified_name()
+    }
+
+    fn value(&mut self, ty: &str) -> Option<String> {
+        Some(match self.next()? {
+            b'n' => "null".to_string(),
+            b'i' => {
+                let n = self.number()?;
+                match ty {
+                    "bool" => (if n == 0 { "false" } else { "true" }).to_string(),
+                    "char" | "wchar" | "dchar" => {
+                        match u32::try_from(n).ok().and_then(char::from_u32) {
+                            Some(c) if c.is_ascii_graphic() || c == ' ' => format!("'{c}'"),
+                            _ => format!("'\\x{n:02X}'"),
+                        }
+                    }
+                    "uint" => format!("{n}u"),
+                    "long" => format!("{n}L"),
+                    "ulong" => format!("{n}uL"),
+                    _ => n.to_string(),
+                }
+            }
+            b'N' => format!("-{}", self.number()?),
+            b'e' => self.hex_float()?,
+            b'c' => {
+                let re = self.hex_float()?;
+                if !self.eat(b'c') {
+                    return None;
+                }
+                format!("{re}+{}i", self.hex_float()?)
+            }
+            width @ (b'a' | b'w' | b'd') => self.string_literal(width)?,
+            b'A' => {
+                let len = self.number()?;
+                let elements: Option<Vec<String>> = (0..len).map(|_| self.value("")).collect();
+                format!("[{}]", elements?.join(", "))
+            }
+            b'H' => {
+                let len = self.number()?;
+                let mut pairs = vec![];
+                for _ in 0..len {
+                    let key = self.value("")?;
+                    pairs.push(format!("{key}:{}", self.value("")?));
+                }
+                format!("[{}]", pairs.join(", "))
+            }
+            b'S' => {
+                let len = self.number()?;
+                let fields: Option<Vec<String>> = (0..len).map(|_| self.value("")).collect();
+                format!("{ty}({})", fields?.join(", "))
+            }
+            _ => return None,
+        })
+    }
+
+    /// `NAN`, `INF`, `NINF` or a hexadecimal mantissa and a decimal exponent after `P`, both
+    /// negative with a leading `N`
+    fn hex_float(&mut self) -> Option<String> {
+        for special in ["NAN", "NINF", "INF"] {
+            if self.buf[self.pos..].starts_with(special.as_bytes()) {
+                self.pos += special.len();
+                return Some(
+                    match special {
+                        "NAN" => "nan",
+                        "NINF" => "-inf",
+                        _ => "inf",
+                    }
+                    .to_string(),
+                );
+            }
+        }
+        let sign = if self.eat(b'N') { "-" } else { "" };
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_hexdigit()) {
+            self.pos += 1;
+        }
+        let mantissa = std::str::from_utf8(&self.buf[start..self.pos]).ok()?;
+        if mantissa.is_empty() || !self.eat(b'P') {
+            return None;
+        }
+        let exponent_sign = if self.eat(b'N') { "-" } else { "" };
+        let exponent = self.number()?;
+        let (first, rest) = mantissa.split_at(1);
+        Some(format!("{sign}0x{first}.{rest}p{exponent_sign}{exponent}"))
+    }
+
+    /// `a`, `w` or `d` string of that character width, the number of code units, `_` and the
+    /// code units in hexadecimal
+    fn string_literal(&mut self, width: u8) -> Option<String> {
+        let len = self.number()?;
+        if !self.eat(b'_') {
+            return None;
+        }
+        let digits = match width {
+            b'a' => 2,
+            b'w' => 4,
+            _ => 8,
+        };
+        let end = self.pos.checked_add(len.checked_mul(digits)?)?;
+        let hex = std::str::from_utf8(self.buf.get(self.pos..end)?).ok()?;
+        self.pos = end;
+        let units: Option<Vec<u32>> = (0..len)
+            .map(|i| u32::from_str_radix(&hex[i * digits..(i + 1) * digits], 16).ok())
+            .collect();
+        let units = units?;
+        let string = match width {
+            b'a' => {
+                let bytes: Option<Vec<u8>> = units.iter().map(|u| u8::try_from(*u).ok()).collect();
+                String::from_utf8_lossy(&bytes?).into_owned()
+            }
+            b'w' => {
+                let units: Option<Vec<u16>> =
+                    units.iter().map(|u| u16::try_from(*u).ok()).collect();
+                String::from_utf16_lossy(&units?)
+            }
+            _ => units
+                .iter()
+                .map(|u| char::from_u32(*u).unwrap_or(char::REPLACEMENT_CHARACTER))
+                .collect(),
+        };
+        let suffix = match width {
+            b'w' => "w",
+            b'd' => "d",
+            _ => "",
+        };
+        Some(format!("{string:?}{suffix}"))
+    }
+
+    fn ty(&mut self) -> Option<String> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return None;
+        }
+        let ty = self.ty_inner();
+        self.depth -= 1;
+        ty
+    }
+
+    fn ty_inner(&mut self) -> Option<String> {
+        let basic = match self.peek()? {
+            b'v' => "void",
+            b'g' => "byte",
+            b'h' => "ubyte",
+            b's' => "short",
+            b't' => "ushort",
+            b'i' => "int",
+            b'k' => "uint",
+            b'l' => "long",
+            b'm' => "ulong",
+            b'f' => "float",
+            b'd' => "double",
+            b'e' => "real",
+            b'o' => "ifloat",
+            b'p' => "idouble",
+            b'j' => "ireal",
+            b'q' => "cfloat",
+            b'r' => "cdouble",
+            b'c' => "creal",
+            b'b' => "bool",
+            b'a' => "char",
+            b'u' => "wchar",
+            b'w' => "dchar",
+            b'n' => "typeof(null)",
+            _ => "",
+        };
+        if !basic.is_empty() {
+            self.pos += 1;
+            return Some(basic.to_string());
+        }
+        let c = self.next()?;
+        Some(match c {
+            b'x' => format!("const({})", self.ty()?),
+            b'y' => format!("immutable({})", self.ty()?),
+            b'O' => format!("shared({})", self.ty()?),
+            b'N' => match self.next()? {
+                b'g' => format!("inout({})", self.ty()?),
+                b'h' => format!("__vector({})", self.ty()?),
+                b'n' => "noreturn".to_string(),
+                _ => return None,
+            },
+            b'Q' => {
+                self.pos -= 1;
+                let target = self.back_reference()?;
+                self.at(target, Self::ty)?
+            }
+            b'A' => format!("{}[]", self.ty()?),
+            b'G' => {
+                let len = self.number()?;
+                format!("{}[{len}]", self.ty()?)
+            }
+            b'H' => {
+                let key = self.ty()?;
+                format!("{}[{key}]", self.ty()?)
+            }
+            b'P' if self.peek().is_some_and(is_call_convention) => {
+                self.function_type("function")?
+            }
+            b'P' => format!("{}*", self.ty()?),
+            b'D' => {
+                let this = self.type_modifiers();
+                let mut delegate = self.function_type("delegate")?;
+                for modifier in this {
+                    delegate.push(' ');
+                    delegate.push_str(modifier);
+                }
+                delegate
+            }
+            b'F' | b'U' | b'W' | b'V' | b'R' | b'Y' => {
+                self.pos -= 1;
+                self.function_type("")?
+            }
+            b'C' | b'S' | b'E' | b'T' => self.full_qualified_name()?,
+            b'I' => self.lname()?,
+            b'B' => {
+                let len = self.number()?;
+                let types: Option<Vec<String>> = (0..len).map(|_| self.parameter()).collect();
+                format!("Tuple!({})", types?.join(", "))
+            }
+            b'z' => match self.next()? {
+                b'i' => "cent".to_string(),
+                b'k' => "ucent".to_string(),
+                _ => return None,
+            },
+            _ => return None,
+        })
+    }
+
+    /// `const`, `immutable`, `shared` and `inout` before a function type
+    fn type_modifiers(&mut self) -> Vec<&'static str> {
+        let mut modifiers = vec![];
+        loop {
+            match (self.peek(), self.peek_at(1)) {
+                (Some(b'x'), _) => modifiers.push("const"),
+                (Some(b'y'), _) => modifiers.push("immutable"),
+                (Some(b'O'), _) => modifiers.push("shared"),
+                (Some(b'N'), Some(b'g')) => {
+                    self.pos += 1;
+                    modifiers.push("inout");
+                }
+                _ => return modifiers,
+            }
+            self.pos += 1;
+        }
+    }
+
+    /// function type with return type, `kind` is `function` or `delegate` for pointers
+    fn function_type(&mut self, kind: &str) -> Option<String> {
+        let function = self.function()?;
+        let ret = self.ty()?;
+        let mut ty = if kind.is_empty() {
+            format!("{ret}{}", function.parameters)
+        } else {
+            format!("{ret} {kind}{}", function.parameters)
+        };
+        for attribute in function.attributes {
+            ty.push(' ');
+            ty.push_str(attribute);
+        }
+        Some(ty)
+    }
+
+    /// function type of a symbol, if one follows, including the modifiers of `this` after `M`
+    fn function_no_return(&mut self) -> Option<Function> {
+        let (start, depth) = (self.pos, self.depth);
+        let this = if self.eat(b'M') {
+            self.type_modifiers()
+        } else {
+            vec![]
+        };
+        let function = self
+            .function()
+            .map(|function| Function { this, ..function });
+        if function.is_none() {
+            self.pos = start;
+            self.depth = depth;
+        }
+        function
+    }
+
+    /// calling convention, attributes and parameters
+    fn function(&mut self) -> Option<Function> {
+        if !self.next().is_some_and(is_call_convention) {
+            return None;
+        }
+        let mut function = Function::default();
+        while self.peek() == Some(b'N') {
+            let attribute = match self.peek_at(1)? {
+                b'a' => "pure",
+                b'b' => "nothrow",
+                b'c' => "ref",
+                b'd' => "@property",
+                b'e' => "@trusted",
+                b'f' => "@safe",
+                b'i' => "@nogc",
+                b'j' => "return",
+                b'l' => "scope",
+                b'm' => "@live",
+                _ => break,
+            };
+            function.attributes.push(attribute);
+            self.pos += 2;
+        }
+        let mut parameters = vec![];
+        let variadic = loop {
+            match self.peek()? {
+                b'X' => break "...",
+                b'Y' if parameters.is_empty() => break "...",
+                b'Y' => break ", ...",
+                b'Z' => break "",
+                _ => parameters.push(self.parameter()?),
+            }
+        };
+        self.pos += 1;
+        function.parameters = format!("({}{variadic})", parameters.join(", "));
+        Some(function)
+    }
+
+    /// parameter type with its storage classes
+    fn parameter(&mut self) -> Option<String> {
+        let mut parameter = String::new();
+        loop {
+            let storage = match (self.peek()?, self.peek_at(1)) {
+                (b'M', _) => "scope ",
+                (b'N', Some(b'k')) => {
+                    self.pos += 1;
+                    "return "
+                }
+                (b'I', _) => "in ",
+                (b'J', _) => "out ",
+                (b'K', _) => "ref ",
+                (b'L', _) => "lazy ",
+                _ => break,
+            };
+            parameter.push_str(storage);
+            self.pos += 1;
+        }
+        parameter.push_str(&self.ty()?);
+        Some(parameter)
+    }
+}
+
+fn is_call_convention(c: u8) -> bool {
+    matches!(c, b'F' | b'U' | b'W' | b'V' | b'R' | b'Y')
+}
@@ -0,0 +1,90 @@
+//! gitignore-style glob filters on relative source paths, applied while parsing
+use regex::RegexSet;
+
+/// Translates a gitignore-style glob into an anchored regex:
+///
+/// - a pattern without `/` (apart from a trailing one) matches in any directory
+/// - a leading `/` anchors the pattern at the base directory
+/// - `*` and `?` do not match `/`, `**` matches across directories
+/// - a pattern naming a directory also matches everything below it
+fn glob_to_regex(glob: &str) -> String {
+    let glob = glob.strip_suffix('/').unwrap_or(glob);
+    let anchored = glob.contains('/');
+    let glob = glob.strip_prefix('/').unwrap_or(glob);
+    let mut re = String::from(if anchored { "^" } else { "^(?:.*/)?" });
+    re.push_str(&glob_body(glob));
+    re.push_str("(?:/.*)?$");
+    re
+}
+
+/// Translates the wildcards of a glob into an unanchored regex, `*` and `?` do not match `/`,
+/// `**` matches across directories and `[!...]` is a negated character class
+pub(crate) fn glob_body(glob: &str) -> String {
+    let mut re = String::new();
+    let chars: Vec<char> = glob.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                if chars.get(i + 2) == Some(&'/') {
+                    re.push_str("(?:.*/)?");
+                    i += 3;
+                } else {
+                    re.push_str(".*");
+                    i += 2;
+                }
+                continue;
+            }
+            '*' => re.push_str("[^/]*"),
+            '?' => re.push_str("[^/]"),
+            '[' => {
+                // character class, copied verbatim with `!` negation translated
+                if let Some(len) = chars[i..].iter().position(|c| *c == ']') {
+                    let class: String = chars[i + 1..i + len].iter().collect();
+                    re.push('[');
+                    re.push_str(&class.replacen('!', "^", usize::from(class.starts_with('!'))));
+                    re.push(']');
+                    i += len + 1;
+                    continue;
+                }
+                re.push_str("\\[");
+            }
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+        i += 1;
+    }
+    re
+}
+
+fn glob_set(globs: &[String]) -> anyhow::Result<Option<RegexSet>> {
+    if globs.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(RegexSet::new(globs.iter().map(|g| glob_to_regex(g)))?))
+}
+
+/// Keeps paths matching any include glob (all if there are none) and no exclude glob
+#[derive(Debug, Default)]
+pub(crate) struct PathFilter {
+    include: Option<RegexSet>,
+    exclude: Option<RegexSet>,
+}
+
+impl PathFilter {
+    pub(crate) fn new(include: &[String], exclude: &[String]) -> anyhow::Result<Self> {
+        Ok(Self {
+            include: glob_set(include)?,
+            exclude: glob_set(exclude)?,
+        })
+    }
+
+    pub(crate) fn keeps(&self, relative_file_name: &str) -> bool {
+        self.include
+            .as_ref()
+            .map_or(true, |set| set.is_match(relative_file_name))
+            && !self
+                .exclude
+                .as_ref()
+                .is_some_and(|set| set.is_match(relative_file_name))
+    }
+}
@@ -0,0 +1,183 @@
+//! Merges instances of one source function, like monomorphized Rust generics or C++ template
+//! instantiations, into one method
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+
+use crate::{Class, CoverageData, Demangler, FunctionHit};
+
+/// Which functions of a source file are merged into one method, their hits are summed and the
+/// smallest mangled name is kept
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MethodGrouping {
+    /// every function is its own method
+    #[default]
+    None,
+    /// functions starting at the same line
+    Line,
+    /// functions with equal demangled names after [`strip_hashes`]
+    Name,
+}
+
+impl std::str::FromStr for MethodGrouping {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Self::None),
+            "line" => Ok(Self::Line),
+            "name" => Ok(Self::Name),
+            _ => anyhow::bail!("unknown method grouping {s:?}, expected none, line or name"),
+        }
+    }
+}
+
+/// Demangled name without the `::h<hash>` suffix of legacy Rust symbols and the
+/// `[clone .cold]` suffixes of compiler generated clones
+#[must_use]
+pub fn strip_hashes(demangled: &str) -> &str {
+    let mut name = demangled.trim_end();
+    while let Some(start) = name.rfind(" [clone ") {
+        if !name.ends_with(']') {
+            break;
+        }
+        name = &name[..start];
+    }
+    match name.rsplit_once("::h") {
+        Some((path, hash)) if hash.len() == 16 && hash.bytes().all(|c| c.is_ascii_hexdigit()) => {
+            path
+        }
+        _ => name,
+    }
+}
+
+/// what the functions of a group have in common
+#[derive(PartialEq, Eq, Hash)]
+enum GroupKey {
+    Line(usize),
+    Name(String),
+}
+
+/// Grouped methods and the number of functions merged into each of them
+pub(crate) type GroupedMethods = (BTreeMap<String, FunctionHit>, BTreeMap<String, usize>);
+
+impl Class {
+    /// methods merged by `grouping`, functions without a known line are not grouped by line
+    pub(crate) fn grouped_methods<D: Demangler>(
+        &self,
+        grouping: MethodGrouping,
+        demangler: &mut D,
+    ) -> io::Result<Option<GroupedMethods>> {
+        if grouping == MethodGrouping::None || self.methods.len() < 2 {
+            return Ok(None);
+        }
+        let mut representatives: HashMap<GroupKey, &str> = HashMap::new();
+        let mut methods: BTreeMap<String, FunctionHit> = BTreeMap::new();
+        let mut instances = self.instances.clone();
+        // sorted, so the first function of a group has the smallest name
+        for (name, (line, hits)) in &self.methods {
+            let key = match grouping {
+                MethodGrouping::Line if *line > 0 => GroupKey::Line(*line),
+                MethodGrouping::Name => {
+                    GroupKey::Name(strip_hashes(&demangler.demangle(name)?).to_string())
+                }
+                _ => GroupKey::Name(name.clone()),
+            };
+            let representative = *representatives.entry(key).or_insert(name.as_str());
+            if representative == name {
+                methods.insert(name.clone(), (*line, *hits));
+                continue;
+            }
+            let merged = instances.remove(name).unwrap_or(1);
+            *instances.entry(representative.to_string()).or_insert(1) += merged;
+            if let Some(method) = methods.get_mut(representative) {
+                if method.0 == 0 || (*line > 0 && *line < method.0) {
+                    method.0 = *line;
+                }
+                method.1 += hits;
+            }
+        }
+        if methods.len() == self.methods.len() {
+            return Ok(None);
+        }
+        Ok(Some((methods, instances)))
+    }
+
+    /// merges the methods in place, see [`Class::grouped_methods`]
+    pub(crate) fn group_methods<D: Demangler>(
+        &mut self,
+        grouping: MethodGrouping,
+        demangler: &mut D,
+    ) -> io::Result<()> {
+        if let Some((methods, instances)) = self.grouped_methods(grouping, demangler)? {
+            self.methods = methods;
+            self.instances = instances;
+        }
+        Ok(())
+    }
+}
+
+impl CoverageData {
+    /// Merges instances of the same source function in every source file, e.g. the `FN` entries
+    /// of monomorphized generics, so function counts are not inflated. The hits of a group are
+    /// summed, the number of merged functions is in [`MethodCoverage::instances`]. `demangler`
+    /// is only used for [`MethodGrouping::Name`].
+    ///
+    /// # Errors
+    ///
+    /// IO errors of the demangler
+    pub fn group_methods<D: Demangler>(
+        &mut self,
+        grouping: MethodGrouping,
+        demangler: &mut D,
+    ) -> io::Result<()> {
+        if grouping == MethodGrouping::Name {
+            demangler.prepare(&self.method_names())?;
+        }
+        for package in self.packages.values_mut() {
+            for class in package.classes.values_mut() {
+                class.group_methods(grouping, demangler)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Coverage of every method, sorted by file and name
+    #[must_use]
+    pub fn methods(&self) -> Vec<MethodCoverage<'_>> {
+        let mut methods: Vec<MethodCoverage<'_>> = self
+            .packages
+            .values()
+            .flat_map(|package| package.classes.iter())
+            .flat_map(|((_, file_name), class)| {
+                class
+                    .methods
+                    .iter()
+                    .map(move |(name, (line, hits))| MethodCoverage {
+                        file_name,
+                        name,
+                        line: *line,
+                        hits: *hits,
+                        instances: class.instances.get(name).copied().unwrap_or(1),
+                    })
+            })
+            .collect();
+        methods.sort_unstable_by(|a, b| (a.file_name, a.name).cmp(&(b.file_name, b.name)));
+        methods
+    }
+}
+
+/// Coverage of one method, see [`CoverageData::methods`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MethodCoverage<'a> {
+    /// relative source file name
+    pub file_name: &'a str,
+    /// mangled name, the smallest one of merged functions
+    pub name: &'a str,
+    /// start line, 0 if unknown
+    pub line: usize,
+    /// hits, summed over merged functions
+    pub hits: usize,
+    /// number of functions merged into this method by [`CoverageData::group_methods`], 1 if
+    /// none were merged
+    pub instances: usize,
+}
@@ -0,0 +1,233 @@
+//! Static HTML report, a browsable alternative to `genhtml` without external assets
+use std::collections::HashSet;
+use std::fmt::Write as _;
+use std::path::Path;
+
+use quick_xml::escape::escape;
+
+use crate::{Class, CompSummary, CoverageData, Demangler, Summary};
+
+const STYLE: &str = r"body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #ccc; padding: 2px 8px; text-align: left; }
+td.num { text-align: right; }
+table.source td { border: none; padding: 0 8px; font-family: monospace; white-space: pre; }
+tr.hit td { background: #dfd; }
+tr.miss td { background: #fdd; }
+tr.partial td { background: #ffd; }
+";
+
+/// Renders `covered/total (rate)` for a table cell
+fn rate_cell(covered: usize, total: usize, rate: f64) -> String {
+    format!(
+        "<td class=\"num\">{:.2}% ({covered}/{total})</td>",
+        rate * 100.
+    )
+}
+
+fn summary_cells(summary: &Summary) -> String {
+    [
+        rate_cell(
+            summary.lines_covered,
+            summary.lines_total,
+            summary.line_rate(),
+        ),
+        rate_cell(
+            summary.branches_covered,
+            summary.branches_total,
+            summary.branch_rate(),
+        ),
+        rate_cell(
+            summary.functions_covered,
+            summary.functions_total,
+            summary.function_rate(),
+        ),
+    ]
+    .concat()
+}
+
+fn page(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\n{STYLE}</style>\n</head>\n<body>\n{body}</body>\n</html>\n",
+        escape(title)
+    )
+}
+
+const TABLE_HEAD: &str = "<th>Lines</th><th>Branches</th><th>Functions</th>";
+
+/// Derives a unique flat page name from the relative source file name
+fn page_name(file_name: &str, taken: &mut HashSet<String>) -> String {
+    let stem: String = file_name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    let mut name = format!("{stem}.html");
+    let mut n = 1;
+    while !taken.insert(name.clone()) {
+        n += 1;
+        name = format!("{stem}_{n}.html");
+    }
+    name
+}
+
+fn file_page<D: Demangler>(
+    file_name: &str,
+    class: &Class,
+    source: Option<&str>,
+    demangler: &mut D,
+) -> anyhow::Result<String> {
+    let mut body = String::new();
+    writeln!(body, "<p><a href=\"../index.html\">Index</a></p>")?;
+    writeln!(body, "<h1>{}</h1>", escape(file_name))?;
+    writeln!(
+        body,
+        "<table>\n<tr>{TABLE_HEAD}</tr>\n<tr>{}</tr>\n</table>",
+        summary_cells(&class.summary())
+    )?;
+
+    writeln!(body, "<h2>Functions</h2>")?;
+    // only files with grouped methods get an instances column
+    let grouped = !class.instances.is_empty();
+    writeln!(
+        body,
+        "<table>\n<tr><th>Function</th><th>Line</th><th>Hits</th>{}</tr>",
+        if grouped { "<th>Instances</th>" } else { "" }
+    )?;
+    let mut methods: Vec<_> = class.methods.iter().collect();
+    methods.sort_by_key(|(_, (line, _))| *line);
+    for (method_name, (line, hits)) in methods {
+        let class_attr = if *hits > 0 { "hit" } else { "miss" };
+        let instances = if grouped {
+            let instances = class.instances.get(method_name).copied().unwrap_or(1);
+            format!("<td class=\"num\">{instances}</td>")
+        } else {
+            String::new()
+        };
+        writeln!(
+            body,
+            "<tr class=\"{class_attr}\"><td>{}</td><td class=\"num\">{line}</td><td class=\"num\">{hits}</td>{instances}</tr>",
+            escape(demangler.demangle(method_name.as_str())?.as_ref())
+        )?;
+    }
+    writeln!(body, "</table>")?;
+
+    writeln!(body, "<h2>Source</h2>")?;
+    if source.is_none() {
+        writeln!(body, "<p>Source file not available.</p>")?;
+    }
+    writeln!(
+        body,
+        "<table class=\"source\">\n<tr><th>Line</th><th>Hits</th><th>Branches</th><th>Source</th></tr>"
+    )?;
+    let source_lines: Vec<&str> = source.map(|s| s.lines().collect()).unwrap_or_default();
+    let last_line = class
+        .lines
+        .keys()
+        .copied()
+        .max()
+        .unwrap_or_default()
+        .max(source_lines.len());
+    for line_number in 1..=last_line {
+        let text = source_lines.get(line_number - 1).copied().unwrap_or("");
+        let (class_attr, hits, branches) = match class.lines.get(&line_number) {
+            Some(line) => {
+                let class_attr = if line.hits == 0 {
+                    "miss"
+                } else if line.branch && line.branches_covered() < line.branches_total() {
+                    "partial"
+                } else {
+                    "hit"
+                };
+                let branches = if line.branch {
+                    format!("{}/{}", line.branches_covered(), line.branches_total())
+                } else {
+                    String::new()
+                };
+                (class_attr, line.hits.to_string(), branches)
+            }
+            None => ("", String::new(), String::new()),
+        };
+        writeln!(
+            body,
+            "<tr class=\"{class_attr}\"><td class=\"num\">{line_number}</td><td class=\"num\">{hits}</td><td class=\"num\">{branches}</td><td>{}</td></tr>",
+            escape(text)
+        )?;
+    }
+    writeln!(body, "</table>")?;
+    Ok(page(file_name, &body))
+}
+
+/// Writes a static HTML report into `out_dir`: an `index.html` with package and file tables and
+/// one page per source file below `out_dir/files`. Sources are read relative to the source root
+/// chosen for their file while parsing, missing sources are reported on the page.
+///
+/// # Errors
+///
+/// - IO Error
+/// - Formatting Error
+pub fn coverage_to_html<P: AsRef<Path>, D: Demangler>(
+    out_dir: P,
+    cov_data: &CoverageData,
+    mut demangler: D,
+) -> anyhow::Result<()> {
+    let out_dir = out_dir.as_ref();
+    let files_dir = out_dir.join("files");
+    std::fs::create_dir_all(&files_dir)?;
+
+    let mut packages: Vec<_> = cov_data.packages.iter().collect();
+    packages.sort_by_key(|(name, _)| *name);
+
+    let mut body = String::new();
+    writeln!(body, "<h1>Coverage report</h1>")?;
+    writeln!(
+        body,
+        "<table>\n<tr>{TABLE_HEAD}</tr>\n<tr>{}</tr>\n</table>",
+        summary_cells(&cov_data.summary())
+    )?;
+
+    writeln!(body, "<h2>Packages</h2>")?;
+    writeln!(body, "<table>\n<tr><th>Package</th>{TABLE_HEAD}</tr>")?;
+    for (pkg_name, package) in &packages {
+        writeln!(
+            body,
+            "<tr><td>{}</td>{}</tr>",
+            escape(pkg_name.as_str()),
+            summary_cells(&package.summary())
+        )?;
+    }
+    writeln!(body, "</table>")?;
+
+    writeln!(body, "<h2>Files</h2>")?;
+    writeln!(body, "<table>\n<tr><th>File</th>{TABLE_HEAD}</tr>")?;
+    let mut taken = HashSet::new();
+    demangler.prepare(&cov_data.method_names())?;
+    for (_, package) in &packages {
+        let mut classes: Vec<_> = package
+            .classes
+            .iter()
+            .map(|(key, class)| (cov_data.class_path(key), &key.1, class))
+            .collect();
+        classes.sort_by(|(a, _, _), (b, _, _)| a.cmp(b));
+        for (file_name, relative_file_name, class) in classes {
+            let file_name = file_name.as_str();
+            let target = page_name(file_name, &mut taken);
+            let source = Path::new(cov_data.source_of(class)).join(relative_file_name);
+            let source = std::fs::read(source)
+                .ok()
+                .map(|bytes| String::from_utf8_lossy(&bytes).into_owned());
+            let html = file_page(file_name, class, source.as_deref(), &mut demangler)?;
+            std::fs::write(files_dir.join(&target), html)?;
+            writeln!(
+                body,
+                "<tr><td><a href=\"files/{target}\">{}</a></td>{}</tr>",
+                escape(file_name),
+                summary_cells(&class.summary())
+            )?;
+        }
+    }
+    writeln!(body, "</table>")?;
+    demangler.stop()?;
+
+    std::fs::write(out_dir.join("index.html"), page("Coverage report", &body))?;
+    Ok(())
+}
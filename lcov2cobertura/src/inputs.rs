@@ -0,0 +1,169 @@
+//! Input files given as lists, e.g. written by `find -print0`, directories or glob patterns
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+
+use crate::filter::glob_body;
+
+/// How directories and glob patterns among the input files are expanded
+#[derive(Debug, Clone)]
+pub struct InputSearch {
+    /// search directories recursively instead of only their files
+    pub recursive: bool,
+    /// glob the file names found in directories have to match, e.g. `*.info`
+    pub name: String,
+}
+
+impl Default for InputSearch {
+    fn default() -> Self {
+        Self {
+            recursive: false,
+            name: "*.info".to_string(),
+        }
+    }
+}
+
+fn has_wildcards(pattern: &str) -> bool {
+    pattern.contains(['*', '?', '['])
+}
+
+fn anchored(glob: &str) -> anyhow::Result<Regex> {
+    Ok(Regex::new(&format!("^{}$", glob_body(glob)))?)
+}
+
+/// Collects the files below `dir` up to `depth` levels deep (unlimited if `None`) with their
+/// path relative to `dir` using `/` as separator. Symbolic links to directories are not
+/// followed.
+fn walk(
+    dir: &Path,
+    relative: &str,
+    depth: Option<usize>,
+    found: &mut Vec<(String, PathBuf)>,
+) -> anyhow::Result<()> {
+    if depth == Some(0) {
+        return Ok(());
+    }
+    let entries = std::fs::read_dir(dir)
+        .map_err(|e| anyhow::anyhow!("cannot read directory {}: {e}", dir.display()))?;
+    for entry in entries {
+        let entry = entry?;
+        let Some(name) = entry.file_name().to_str().map(str::to_string) else {
+            continue;
+        };
+        let name = if relative.is_empty() {
+            name
+        } else {
+            format!("{relative}/{name}")
+        };
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            walk(&path, &name, depth.map(|d| d - 1), found)?;
+        } else if path.is_file() {
+            found.push((name, path));
+        }
+    }
+    Ok(())
+}
+
+/// Files of a directory input whose name matches [`InputSearch::name`]
+fn expand_dir(dir: &Path, search: &InputSearch) -> anyhow::Result<Vec<PathBuf>> {
+    let name = anchored(&search.name)?;
+    let mut found = vec![];
+    walk(dir, "", (!search.recursive).then_some(1), &mut found)?;
+    let mut files: Vec<PathBuf> = found
+        .into_iter()
+        .filter(|(relative, _)| name.is_match(relative.rsplit('/').next().unwrap_or(relative)))
+        .map(|(_, path)| path)
+        .collect();
+    anyhow::ensure!(
+        !files.is_empty(),
+        "no files named {} in {}",
+        search.name,
+        dir.display()
+    );
+    files.sort_unstable();
+    Ok(files)
+}
+
+/// Files matching a glob pattern, the directories before the first wildcard are the root of
+/// the search. `/` separates directories on all platforms.
+fn expand_glob(pattern: &str) -> anyhow::Result<Vec<PathBuf>> {
+    let parts: Vec<&str> = pattern.split(['/', std::path::MAIN_SEPARATOR]).collect();
+    let literal = parts.iter().take_while(|part| !has_wildcards(part)).count();
+    let root = parts[..literal].join("/");
+    let glob = parts[literal..].join("/");
+    let depth = (!glob.contains("**")).then_some(parts.len() - literal);
+    let dir = match root.as_str() {
+        "" if literal > 0 => Path::new("/"),
+        "" => Path::new("."),
+        root => Path::new(root),
+    };
+    let mut found = vec![];
+    if dir.is_dir() {
+        walk(dir, "", depth, &mut found)?;
+    }
+    let glob_re = anchored(&glob)?;
+    let mut files: Vec<PathBuf> = found
+        .into_iter()
+        .filter(|(relative, _)| glob_re.is_match(relative))
+        // a pattern without directory prefix yields `a/lcov.info` instead of `./a/lcov.info`
+        .map(|(relative, path)| if literal == 0 { relative.into() } else { path })
+        .collect();
+    anyhow::ensure!(!files.is_empty(), "no input files match {pattern}");
+    files.sort_unstable();
+    Ok(files)
+}
+
+/// Replaces directories among `inputs` with their files named like [`InputSearch::name`] and
+/// glob patterns like `target/**/lcov.info` with the files matching them, sorted by path.
+/// Existing files, `-` and paths without wildcards are kept as they are, a file found twice is
+/// kept once.
+///
+/// # Errors
+///
+/// A directory without matching files, a pattern matching nothing, an invalid pattern or IO
+/// errors reading a directory.
+pub fn expand_inputs(inputs: &[PathBuf], search: &InputSearch) -> anyhow::Result<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = vec![];
+    for input in inputs {
+        let expanded = if input.is_dir() {
+            expand_dir(input, search)?
+        } else if input.exists() || !input.to_str().is_some_and(has_wildcards) {
+            vec![input.clone()]
+        } else {
+            expand_glob(input.to_str().unwrap_or_default())?
+        };
+        for file in expanded {
+            if !files.contains(&file) {
+                files.push(file);
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Splits a list of paths at NUL bytes if it has any, otherwise at line ends. Empty entries
+/// are skipped, `\r\n` line ends are accepted.
+///
+/// # Errors
+///
+/// A path that is not UTF-8.
+pub fn read_file_list(content: &[u8]) -> anyhow::Result<Vec<PathBuf>> {
+    let separator = if content.contains(&0) { 0 } else { b'\n' };
+    content
+        .split(|c| *c == separator)
+        .map(|entry| {
+            if separator == b'\n' {
+                entry.strip_suffix(b"\r").unwrap_or(entry)
+            } else {
+                entry
+            }
+        })
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let path = std::str::from_utf8(entry)
+                .map_err(|_| anyhow::anyhow!("file list entry is not UTF-8: {entry:?}"))?;
+            Ok(PathBuf::from(path))
+        })
+        .collect()
+}
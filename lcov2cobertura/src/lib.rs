@@ -6,12 +6,60 @@ use std::path::Path;
 use quick_xml::events::{BytesEnd, BytesStart, BytesText, Event};
 use quick_xml::writer::Writer;
 
+mod cobertura;
 mod cobertura_split;
+mod compare;
 mod demangle;
+mod diff;
+mod dlang;
+mod filter;
+mod grouping;
+#[cfg(feature = "html")]
+mod html;
+mod inputs;
+mod naming;
+#[cfg(feature = "parallel")]
+mod parallel;
+mod path_map;
+mod rewrite;
+mod signature;
+mod streaming;
+mod summary;
 mod tests;
+mod thresholds;
+mod validate;
 
+pub use cobertura::{parse_cobertura, parse_cobertura_file};
 pub use cobertura_split::corbertura_xml_split;
-pub use demangle::{CppDemangler, Demangler, NullDemangler, RustDemangler};
+pub use compare::{compare, CompareFormat, Comparison, SummaryDelta};
+#[cfg(feature = "cpp-demangle")]
+pub use demangle::ItaniumDemangler;
+pub use demangle::{
+    AutoDemangler, CachingDemangler, CppDemangler, DDemangler, Demangler, DemanglerKind,
+    DemanglerOptions, NullDemangler, RustDemangler, SwiftDemangler, SymbolKind,
+};
+pub use diff::{
+    diff_coverage, parse_unified_diff, AmbiguousMatch, ChangedLines, DiffCoverage, FileDiffCoverage,
+};
+pub use grouping::{strip_hashes, MethodCoverage, MethodGrouping};
+#[cfg(feature = "html")]
+pub use html::coverage_to_html;
+pub use inputs::{expand_inputs, read_file_list, InputSearch};
+pub use naming::PackageNaming;
+#[cfg(feature = "parallel")]
+pub use parallel::{demangle_parallel, parse_files_parallel};
+pub use path_map::{PathMap, PathStyle};
+pub use rewrite::{RewritePreset, RewriteRule, RewritingDemangler};
+pub use signature::{split_signature, MethodNames};
+pub use streaming::{stream_to_file, stream_xml, StreamStats};
+pub use summary::{
+    render_summary, summary_coverage_line, total_coverage_line, SummaryFormat, SummaryOptions,
+    SummarySort,
+};
+pub use thresholds::{
+    check_thresholds, ThresholdFailure, ThresholdRule, ThresholdScope, Thresholds,
+};
+pub use validate::{validate, Problem};
 
 #[allow(clippy::cast_precision_loss)]
 fn percent(a: usize, b: usize) -> f64 {
@@ -24,20 +72,95 @@ fn percent(a: usize, b: usize) -> f64 {
 
 /// Summary of coverage info
 #[derive(Debug, Default, Clone)]
-struct Summary {
-    lines_total: usize,
-    lines_covered: usize,
-    branches_total: usize,
-    branches_covered: usize,
+pub struct Summary {
+    /// number of instrumented lines
+    pub lines_total: usize,
+    /// number of lines hit at least once
+    pub lines_covered: usize,
+    /// number of branches
+    pub branches_total: usize,
+    /// number of branches taken at least once
+    pub branches_covered: usize,
+    /// number of functions
+    pub functions_total: usize,
+    /// number of functions called at least once
+    pub functions_covered: usize,
 }
 
 impl Summary {
-    fn branch_rate(&self) -> f64 {
+    /// ratio of covered branches, between 0 and 1
+    #[must_use]
+    pub fn branch_rate(&self) -> f64 {
         percent(self.branches_total, self.branches_covered)
     }
-    fn line_rate(&self) -> f64 {
+    /// ratio of covered lines, between 0 and 1
+    #[must_use]
+    pub fn line_rate(&self) -> f64 {
         percent(self.lines_total, self.lines_covered)
     }
+    /// ratio of covered functions, between 0 and 1
+    #[must_use]
+    pub fn function_rate(&self) -> f64 {
+        percent(self.functions_total, self.functions_covered)
+    }
+}
+
+/// Coverage metric of a [`Summary`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Metric {
+    /// line coverage
+    #[default]
+    Lines,
+    /// branch coverage
+    Branches,
+    /// function coverage
+    Functions,
+}
+
+impl Metric {
+    /// all metrics in their default order
+    pub const ALL: [Self; 3] = [Self::Lines, Self::Branches, Self::Functions];
+
+    /// singular name used in human readable output
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Lines => "line",
+            Self::Branches => "branch",
+            Self::Functions => "function",
+        }
+    }
+}
+
+impl std::str::FromStr for Metric {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lines" | "line" => Ok(Self::Lines),
+            "branches" | "branch" => Ok(Self::Branches),
+            "functions" | "function" => Ok(Self::Functions),
+            _ => anyhow::bail!("unknown metric {s:?}, expected lines, branches or functions"),
+        }
+    }
+}
+
+impl Summary {
+    /// covered and total count of the given metric
+    #[must_use]
+    pub fn counts(&self, metric: Metric) -> (usize, usize) {
+        match metric {
+            Metric::Lines => (self.lines_covered, self.lines_total),
+            Metric::Branches => (self.branches_covered, self.branches_total),
+            Metric::Functions => (self.functions_covered, self.functions_total),
+        }
+    }
+    /// ratio of the given metric, between 0 and 1
+    #[must_use]
+    pub fn rate(&self, metric: Metric) -> f64 {
+        let (covered, total) = self.counts(metric);
+        percent(total, covered)
+    }
 }
 
 impl std::ops::Add for Summary {
@@ -49,6 +172,8 @@ impl std::ops::Add for Summary {
             lines_covered: self.lines_covered + other.lines_covered,
             branches_total: self.branches_total + other.branches_total,
             branches_covered: self.branches_covered + other.branches_covered,
+            functions_total: self.functions_total + other.functions_total,
+            functions_covered: self.functions_covered + other.functions_covered,
         }
     }
 }
@@ -62,44 +187,34 @@ impl std::iter::Sum<Self> for Summary {
     }
 }
 
-trait CompSummary {
+/// Computes the coverage [`Summary`] of a node in the coverage tree
+pub trait CompSummary {
+    /// Aggregated summary of this node and all its children
     fn summary(&self) -> Summary;
 }
 
+/// Identifies a class: index of its source root and the file name relative to that root, so
+/// files with the same relative path below different roots stay apart
+type ClassKey = (usize, String);
+
 /// Package data of coverage info
 #[derive(Debug, Default)]
 struct Package {
-    classes: HashMap<String, Class>,
+    classes: HashMap<ClassKey, Class>,
 }
 
 impl Package {
-    fn insert_class(&mut self, relative_file_name: &str) -> Option<Class> {
-        let class = Class::from_fn(relative_file_name);
-        self.classes.insert(relative_file_name.to_owned(), class)
+    /// keeps an existing class, so records of the same file merge
+    fn insert_class(&mut self, key: &ClassKey) {
+        if !self.classes.contains_key(key) {
+            self.classes.insert(key.clone(), Class::from_key(key));
+        }
     }
 }
 
 impl CompSummary for Package {
     fn summary(&self) -> Summary {
-        let lines_total = self.classes.values().map(|c| c.lines.len()).sum();
-        let lines_covered = self.classes.values().map(|c| c.lines_covered).sum();
-        let branches_total = self
-            .classes
-            .values()
-            .map(|c| c.lines.values().map(|b| b.branches_total).sum::<usize>())
-            .sum();
-        let branches_covered = self
-            .classes
-            .values()
-            .map(|c| c.lines.values().map(|b| b.branches_covered).sum::<usize>())
-            .sum();
-
-        Summary {
-            lines_total,
-            lines_covered,
-            branches_total,
-            branches_covered,
-        }
+        self.classes.values().map(CompSummary::summary).sum()
     }
 }
 
@@ -111,38 +226,74 @@ pub struct Class {
     name: String,
     lines: HashMap<usize, Branch>,
     methods: BTreeMap<String, FunctionHit>, // for deterministic sorted XML
+    instances: BTreeMap<String, usize>,     // functions merged into a method, if more than one
     lines_covered: usize,
+    source: usize, // index into the sources the file name is relative to
 }
 
 impl Class {
     fn from_fn(relative_file_name: &str) -> Self {
-        let elems = relative_file_name
-            .split(std::path::MAIN_SEPARATOR)
-            .collect::<Vec<&str>>();
+        let elems = relative_file_name.split(['/', '\\']).collect::<Vec<&str>>();
         let name = elems.join(".");
         Self {
             name,
             ..Self::default()
         }
     }
+
+    fn from_key((source, relative_file_name): &ClassKey) -> Self {
+        Self {
+            source: *source,
+            ..Self::from_fn(relative_file_name)
+        }
+    }
+}
+
+impl Class {
+    fn merge(&mut self, other: Self) {
+        for (line_number, line) in other.lines {
+            let target = self.lines.entry(line_number).or_default();
+            target.hits += line.hits;
+            target.branch |= line.branch;
+            for ((block, branch), hits) in line.outcomes {
+                target.add_outcome(block, branch, hits);
+            }
+        }
+        for (name, (line, hits)) in other.methods {
+            self.methods.entry(name).or_insert((line, 0)).1 += hits;
+        }
+        for (name, instances) in other.instances {
+            let target = self.instances.entry(name).or_default();
+            *target = (*target).max(instances);
+        }
+        self.lines_covered = self.lines.values().filter(|line| line.hits > 0).count();
+    }
 }
 
 impl CompSummary for Class {
     fn summary(&self) -> Summary {
         let lines_total = self.lines.len();
         let lines_covered = self.lines_covered;
-        let branches_total = self.lines.values().map(|b| b.branches_total).sum::<usize>();
+        let branches_total = self
+            .lines
+            .values()
+            .map(Branch::branches_total)
+            .sum::<usize>();
         let branches_covered = self
             .lines
             .values()
-            .map(|b| b.branches_covered)
+            .map(Branch::branches_covered)
             .sum::<usize>();
+        let functions_total = self.methods.len();
+        let functions_covered = self.methods.values().filter(|(_, hits)| *hits > 0).count();
 
         Summary {
             lines_total,
             lines_covered,
             branches_total,
             branches_covered,
+            functions_total,
+            functions_covered,
         }
     }
 }
@@ -152,17 +303,34 @@ impl CompSummary for Class {
 pub struct Branch {
     #[allow(clippy::struct_field_names)]
     branch: bool,
-    branches_total: usize,
-    branches_covered: usize,
+    outcomes: BTreeMap<(usize, usize), usize>, // hits by block and branch number
     hits: usize,
 }
 
+impl Branch {
+    /// adds the hits of one branch, runs of the same branch sum up like `lcov` merges them
+    fn add_outcome(&mut self, block: usize, branch: usize, hits: usize) {
+        self.branch = true;
+        *self.outcomes.entry((block, branch)).or_default() += hits;
+    }
+
+    fn branches_total(&self) -> usize {
+        self.outcomes.len()
+    }
+
+    fn branches_covered(&self) -> usize {
+        self.outcomes.values().filter(|hits| **hits > 0).count()
+    }
+}
+
 /// Coverage information collected while parsing
 #[derive(Debug, Default)]
 pub struct CoverageData {
     packages: HashMap<String, Package>,
     base_dir: String,
-    cdsummary: Summary, // FIXME remove this, done by summary trait!?
+    source_roots: Vec<String>, // additional sources after base_dir
+    cdsummary: Summary,        // FIXME remove this, done by summary trait!?
+    filtered_records: usize,
 }
 
 impl CompSummary for CoverageData {
@@ -176,41 +344,45 @@ impl CompSummary for CoverageData {
 // panic though.
 #[allow(clippy::unwrap_used)]
 impl CoverageData {
+    /// adds the hits, returns whether the line was not covered before
     fn update_line_hits(
         &mut self,
         package_name: &str,
-        relative_file_name: &str,
+        class_key: &ClassKey,
         line_number: usize,
         line_hits: usize,
-    ) {
+    ) -> bool {
         // avoid allocation if entry exists
-        if let Some(class) = self
+        let line = if let Some(class) = self
             .packages
             .get_mut(package_name)
             .unwrap()
             .classes
-            .get_mut(relative_file_name)
+            .get_mut(class_key)
         {
-            class.lines.entry(line_number).or_default().hits = line_hits;
+            class.lines.entry(line_number).or_default()
         } else {
             self.packages
                 .get_mut(package_name)
                 .unwrap()
                 .classes
-                .entry(relative_file_name.to_owned())
-                .or_insert_with(|| Class::from_fn(relative_file_name))
+                .entry(class_key.clone())
+                .or_insert_with(|| Class::from_key(class_key))
                 .lines
                 .entry(line_number)
                 .or_default()
-                .hits = line_hits;
-        }
+        };
+        let newly_covered = line.hits == 0 && line_hits > 0;
+        line.hits += line_hits;
+        newly_covered
     }
 
     fn inc_branches(
         &mut self,
         package_name: &str,
-        class_name: &str,
+        class_key: &ClassKey,
         line_number: usize,
+        (block, branch): (usize, usize),
         branch_hits: usize,
     ) {
         // Insert default if missing to ensure entry exists
@@ -218,7 +390,7 @@ impl CoverageData {
             .get_mut(package_name)
             .unwrap()
             .classes
-            .get_mut(class_name)
+            .get_mut(class_key)
             .unwrap()
             .lines
             .entry(line_number)
@@ -228,23 +400,17 @@ impl CoverageData {
             .get_mut(package_name)
             .unwrap()
             .classes
-            .get_mut(class_name)
+            .get_mut(class_key)
             .unwrap()
             .lines
             .entry(line_number)
-            .and_modify(|branch| {
-                branch.branch = true;
-                branch.branches_total += 1;
-                if branch_hits > 0 {
-                    branch.branches_covered += 1;
-                }
-            });
+            .and_modify(|line| line.add_outcome(block, branch, branch_hits));
     }
 
     fn insert_method(
         &mut self,
         package_name: &str,
-        class_name: &str,
+        class_key: &ClassKey,
         method_name: &str,
         method_line: usize,
     ) {
@@ -252,16 +418,24 @@ impl CoverageData {
             .get_mut(package_name)
             .unwrap()
             .classes
-            .get_mut(class_name)
+            .get_mut(class_key)
             .unwrap()
             .methods
-            .insert(method_name.to_owned(), (method_line, 0));
+            .entry(method_name.to_owned())
+            // keeps the hits of an earlier record of the same file, or of an `FNDA` before the
+            // `FN`
+            .and_modify(|e| {
+                if e.0 == 0 {
+                    e.0 = method_line;
+                }
+            })
+            .or_insert((method_line, 0));
     }
 
     fn update_method_hits(
         &mut self,
         package_name: &str,
-        class_name: &str,
+        class_key: &ClassKey,
         method_name: &str,
         method_hits: usize,
     ) {
@@ -269,20 +443,20 @@ impl CoverageData {
             .get_mut(package_name)
             .unwrap()
             .classes
-            .get_mut(class_name)
+            .get_mut(class_key)
             .unwrap()
             .methods
             .entry(method_name.to_owned())
-            .and_modify(|e| e.1 = method_hits)
+            .and_modify(|e| e.1 += method_hits)
             .or_insert((0, method_hits));
     }
 
-    fn inc_lines_covered(&mut self, package_name: &str, class_name: &str) {
+    fn inc_lines_covered(&mut self, package_name: &str, class_key: &ClassKey) {
         self.packages
             .get_mut(package_name)
             .unwrap()
             .classes
-            .get_mut(class_name)
+            .get_mut(class_key)
             .unwrap()
             .lines_covered += 1;
     }
@@ -290,6 +464,122 @@ impl CoverageData {
     fn inc_lines_total(&mut self) {
         self.cdsummary.lines_total += 1;
     }
+
+    /// remove unwanted packages
+    fn remove_excluded(&mut self, excludes: &[&str]) -> anyhow::Result<()> {
+        let mut to_remove = vec![];
+        let excludes: Result<Vec<regex::Regex>, _> =
+            excludes.iter().map(|v| regex::Regex::new(v)).collect();
+        let excludes = excludes?;
+        for pkg_key in self.packages.keys() {
+            for re in &excludes {
+                if re.is_match(pkg_key) {
+                    to_remove.push(pkg_key.to_owned());
+                }
+            }
+        }
+        for ex in to_remove {
+            self.packages.remove(&ex);
+        }
+        Ok(())
+    }
+
+    /// unique function names of all classes, sorted
+    #[must_use]
+    pub fn method_names(&self) -> Vec<&str> {
+        let names: std::collections::BTreeSet<&str> = self
+            .packages
+            .values()
+            .flat_map(|package| package.classes.values())
+            .flat_map(|class| class.methods.keys().map(String::as_str))
+            .collect();
+        names.into_iter().collect()
+    }
+
+    /// source roots, starting with `base_dir`, written as `<source>` elements
+    pub fn sources(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.base_dir.as_str()).chain(self.source_roots.iter().map(String::as_str))
+    }
+
+    /// source root the file name of a class is relative to
+    pub(crate) fn source_of(&self, class: &Class) -> &str {
+        self.sources().nth(class.source).unwrap_or(&self.base_dir)
+    }
+
+    /// source root the relative source file name was stripped against, the first one of the
+    /// `<source>` elements if the name is found below several roots, `None` for unknown files
+    #[must_use]
+    pub fn class_source(&self, file_name: &str) -> Option<&str> {
+        self.packages
+            .values()
+            .flat_map(|package| package.classes.keys())
+            .filter(|(_, name)| name == file_name)
+            .map(|(source, _)| *source)
+            .min()
+            .and_then(|source| self.sources().nth(source))
+    }
+
+    /// name of a class in reports: its relative file name, prefixed with its source root
+    /// (relative to `base_dir` if below it) if that is not `base_dir`, so files with the same
+    /// relative name below different roots are told apart
+    pub(crate) fn class_path(&self, (source, file_name): &ClassKey) -> String {
+        let Some(root) = self.sources().nth(*source).filter(|_| *source > 0) else {
+            return file_name.clone();
+        };
+        let root = Path::new(root)
+            .strip_prefix(&self.base_dir)
+            .ok()
+            .and_then(Path::to_str)
+            .filter(|relative| !relative.is_empty())
+            .unwrap_or(root);
+        format!("{}/{file_name}", root.trim_end_matches(['/', '\\']))
+    }
+
+    /// source root written as `source` attribute of a class, only if there are several roots
+    pub(crate) fn class_root(&self, class: &Class) -> Option<&str> {
+        (!self.source_roots.is_empty()).then(|| self.source_of(class))
+    }
+
+    /// Merges the coverage of another run into this one: line, branch and function hits are
+    /// summed, sources are united. Branches are told apart by their block and branch number,
+    /// so the merged branch coverage equals that of merging the tracefiles with `lcov`.
+    pub fn merge(&mut self, other: CoverageData) {
+        if self.base_dir.is_empty() {
+            self.base_dir.clone_from(&other.base_dir);
+        }
+        // index of each source of `other` in the merged sources
+        let mut source_index = vec![];
+        for source in other.sources() {
+            let index = self.sources().position(|s| s == source);
+            source_index.push(index.unwrap_or_else(|| {
+                self.source_roots.push(source.to_string());
+                self.source_roots.len()
+            }));
+        }
+        for (pkg_name, package) in other.packages {
+            let target = self.packages.entry(pkg_name).or_default();
+            for ((_, file_name), mut class) in package.classes {
+                class.source = source_index.get(class.source).copied().unwrap_or(0);
+                match target.classes.entry((class.source, file_name)) {
+                    std::collections::hash_map::Entry::Vacant(entry) => {
+                        entry.insert(class);
+                    }
+                    std::collections::hash_map::Entry::Occupied(mut entry) => {
+                        entry.get_mut().merge(class);
+                    }
+                }
+            }
+        }
+        self.cdsummary = std::mem::take(&mut self.cdsummary) + other.cdsummary;
+        self.filtered_records += other.filtered_records;
+    }
+
+    /// number of records skipped by the include and file exclude globs or for being outside
+    /// `base_dir`, see [`ParseOptions`]
+    #[must_use]
+    pub fn filtered_records(&self) -> usize {
+        self.filtered_records
+    }
 }
 
 /// parses from filename
@@ -308,57 +598,144 @@ pub fn parse_file<P: AsRef<Path>>(
     parse_lines(lines, base_dir, excludes)
 }
 
+/// Options applied while parsing, see [`parse_lines_with`]
+#[derive(Debug, Default, Clone)]
+pub struct ParseOptions {
+    /// gitignore-style globs on the relative source path, only matching records are kept
+    /// (all if empty)
+    pub include: Vec<String>,
+    /// gitignore-style globs on the relative source path, matching records are dropped
+    pub exclude_file: Vec<String>,
+    /// rewrite rules for `SF` paths, the first matching one is applied before `base_dir` is
+    /// stripped
+    pub path_maps: Vec<PathMap>,
+    /// drop records whose mapped path is not below `base_dir`, e.g. system headers
+    pub drop_outside_base_dir: bool,
+    /// resolve symlinks of `base_dir` and existing source files, by default paths are only
+    /// normalized lexically
+    pub canonicalize_paths: bool,
+    /// separator style of the relative source path in the `filename` attribute
+    pub path_style: PathStyle,
+    /// how packages are named after the relative source path
+    pub package_naming: PackageNaming,
+    /// further source roots besides `base_dir`, each `SF` path is made relative to the longest
+    /// root containing it
+    pub source_roots: Vec<String>,
+}
+
+/// parses from filename with [`ParseOptions`]
+///
+/// # Errors
+///
+/// Either IO error or errors parsing the lines.
+pub fn parse_file_with<P: AsRef<Path>>(
+    filename: P,
+    base_dir: P,
+    excludes: &[&str],
+    options: &ParseOptions,
+) -> anyhow::Result<CoverageData> {
+    let file = std::fs::File::open(filename)?;
+    let lines = std::io::BufReader::new(file).lines();
+
+    parse_lines_with(lines, base_dir, excludes, options)
+}
+
 /// parses from iterator
 ///
 /// # Errors
 ///
 /// Either IO error or errors parsing the lines.
-#[allow(clippy::too_many_lines, reason = "TODO: FIXME")]
 pub fn parse_lines<P: AsRef<Path>, B: BufRead>(
     lines: Lines<B>,
     base_dir: P,
     excludes: &[&str],
 ) -> anyhow::Result<CoverageData> {
-    let base_dir: &Path = base_dir.as_ref();
+    parse_lines_with(lines, base_dir, excludes, &ParseOptions::default())
+}
+
+/// parses from iterator, records whose relative source path is rejected by the include and
+/// file exclude globs of `options` are skipped without being stored
+///
+/// # Errors
+///
+/// Either IO error, invalid globs or errors parsing the lines.
+pub fn parse_lines_with<P: AsRef<Path>, B: BufRead>(
+    lines: Lines<B>,
+    base_dir: P,
+    excludes: &[&str],
+    options: &ParseOptions,
+) -> anyhow::Result<CoverageData> {
+    let mut cov_data = parse_records(lines, base_dir.as_ref(), options, |_, _, _| Ok(()))?;
+    cov_data.remove_excluded(excludes)?;
+    Ok(cov_data)
+}
+
+/// Parses all records, `record_end` is called with the package and relative file name of every
+/// kept record once its `end_of_record` line is read
+#[allow(clippy::too_many_lines, reason = "TODO: FIXME")]
+fn parse_records<B: BufRead, F>(
+    lines: Lines<B>,
+    base_dir: &Path,
+    options: &ParseOptions,
+    mut record_end: F,
+) -> anyhow::Result<CoverageData>
+where
+    F: FnMut(&mut CoverageData, &str, &ClassKey) -> anyhow::Result<()>,
+{
+    let filter = filter::PathFilter::new(&options.include, &options.exclude_file)?;
     let mut cov_data = CoverageData {
         base_dir: base_dir
             .to_str()
             .ok_or_else(|| anyhow::anyhow!("base_dir cannot be converted to string"))?
             .to_string(),
+        source_roots: options.source_roots.clone(),
         ..Default::default()
     };
+    let resolver = path_map::PathResolver::new(cov_data.sources(), options.canonicalize_paths);
     let mut relative_file_name = String::new();
+    let mut class_key: ClassKey = (0, String::new());
     let mut package_name = String::new();
+    let mut skip_record = false;
     // TODO use https://docs.rs/lcov/latest/lcov/ existing parser
     for line in lines {
         let line = line?;
         let mut split = line.splitn(2, ':');
         let (input_type, line) = (split.next(), split.last());
+        if skip_record {
+            skip_record = input_type != Some("end_of_record");
+            continue;
+        }
 
         #[allow(clippy::match_same_arms, reason = "easier to read the doc-strings")]
         match input_type {
             Some("SF") => {
                 let file_name = line.ok_or_else(|| anyhow::anyhow!("SF entry has no filename"))?;
-                let file_path = Path::new(file_name);
-                // TODO: was `relative_file_name = os.path.relpath(file_name, self.base_dir)`
-                // does not do the same as strip_prefix, but I am fairly certain it was the idea
-                file_path
-                    .strip_prefix(base_dir)
-                    .unwrap_or(file_path)
+                let file_name = path_map::portable(file_name);
+                let file_name = path_map::map_path(&options.path_maps, &file_name);
+                // like `relative_file_name = os.path.relpath(file_name, self.base_dir)`, so
+                // equivalent spellings of a path end up in the same class
+                let (relative_path, source, outside) = resolver.relative(&file_name);
+                relative_path
                     .to_str()
                     .ok_or_else(|| {
                         anyhow::anyhow!("relative_file_name cannot be converted to string")
                     })?
                     .clone_into(&mut relative_file_name);
-                let elems = relative_file_name
-                    .split(std::path::MAIN_SEPARATOR)
-                    .collect::<Vec<&str>>();
-                package_name = elems[..elems.len() - 1].join(".");
+                if (options.drop_outside_base_dir && outside)
+                    || !filter.keeps(&relative_file_name.replace(std::path::MAIN_SEPARATOR, "/"))
+                {
+                    cov_data.filtered_records += 1;
+                    skip_record = true;
+                    continue;
+                }
+                options.path_style.apply(&mut relative_file_name);
+                package_name = options.package_naming.package_name(&relative_file_name);
+                class_key = (source, relative_file_name.clone());
                 cov_data
                     .packages
                     .entry(package_name.clone())
                     .or_default()
-                    .insert_class(&relative_file_name);
+                    .insert_class(&class_key);
             }
             Some("DA") => {
                 let mut split = line
@@ -368,21 +745,21 @@ pub fn parse_lines<P: AsRef<Path>, B: BufRead>(
                 if let (Some(number), Some(hits)) = (line_number, line_hits) {
                     let line_number: usize = number.parse()?;
                     let line_hits = hits.parse::<usize>().unwrap_or(0);
-                    cov_data.update_line_hits(
+                    let newly_covered = cov_data.update_line_hits(
                         &package_name,
-                        &relative_file_name,
+                        &class_key,
                         line_number,
                         line_hits,
                     );
 
-                    if line_hits > 0 {
-                        cov_data.inc_lines_covered(&package_name, &relative_file_name);
+                    if newly_covered {
+                        cov_data.inc_lines_covered(&package_name, &class_key);
                     }
                     cov_data.inc_lines_total();
                 }
             }
             Some("BRDA") => {
-                if let [line_number, _block_number, _branch_number, branch_hits] = line
+                if let [line_number, block_number, branch_number, branch_hits] = line
                     .ok_or_else(|| anyhow::anyhow!("BRDA entry has no fields"))?
                     .splitn(4, ',')
                     .map(|v| v.parse::<usize>().unwrap_or(0))
@@ -391,8 +768,9 @@ pub fn parse_lines<P: AsRef<Path>, B: BufRead>(
                 {
                     cov_data.inc_branches(
                         &package_name,
-                        &relative_file_name,
+                        &class_key,
                         *line_number,
+                        (*block_number, *branch_number),
                         *branch_hits,
                     );
                 }
@@ -416,12 +794,7 @@ pub fn parse_lines<P: AsRef<Path>, B: BufRead>(
                 let (function_line, function_name) = (split.next(), split.last());
                 if let (Some(function_line), Some(function_name)) = (function_line, function_name) {
                     let function_line: usize = function_line.parse()?;
-                    cov_data.insert_method(
-                        &package_name,
-                        &relative_file_name,
-                        function_name,
-                        function_line,
-                    );
+                    cov_data.insert_method(&package_name, &class_key, function_name, function_line);
                 }
             }
             Some("FNDA") => {
@@ -433,13 +806,15 @@ pub fn parse_lines<P: AsRef<Path>, B: BufRead>(
                     let function_hits: usize = function_hits.parse()?;
                     cov_data.update_method_hits(
                         &package_name,
-                        &relative_file_name,
+                        &class_key,
                         function_name,
                         function_hits,
                     );
                 }
             }
-            Some("end_of_record") => (),
+            Some("end_of_record") => {
+                record_end(&mut cov_data, &package_name, &class_key)?;
+            }
             Some("FNF") => (), // FIXME in real world data
             Some("FNH") => (), // FIXME in real world data
             Some("LF") => (),  // FIXME in real world data
@@ -450,21 +825,6 @@ pub fn parse_lines<P: AsRef<Path>, B: BufRead>(
             None => anyhow::bail!("no input type for this line"),
         }
     }
-    // remove unwanted packages
-    let mut to_remove = vec![];
-    let excludes: Result<Vec<regex::Regex>, _> =
-        excludes.iter().map(|v| regex::Regex::new(v)).collect();
-    let excludes = excludes?;
-    for pkg_key in cov_data.packages.keys() {
-        for re in &excludes {
-            if re.is_match(pkg_key) {
-                to_remove.push(pkg_key.to_owned());
-            }
-        }
-    }
-    for ex in to_remove {
-        cov_data.packages.remove(&ex);
-    }
     Ok(cov_data)
 }
 
@@ -474,6 +834,132 @@ macro_rules! s {
     };
 }
 
+/// `<coverage>` start tag with the totals
+fn coverage_start(summary: &Summary, timestamp: u64) -> BytesStart<'static> {
+    let mut elem = BytesStart::new("coverage");
+    elem.push_attribute(("branch-rate", s!(summary.branch_rate())));
+    elem.push_attribute(("branches-covered", s!(summary.branches_covered)));
+    elem.push_attribute(("branches-valid", s!(summary.branches_total)));
+    elem.push_attribute(("complexity", "0"));
+    elem.push_attribute(("line-rate", s!(summary.line_rate())));
+    elem.push_attribute(("lines-covered", s!(summary.lines_covered)));
+    elem.push_attribute(("lines-valid", s!(summary.lines_total)));
+    elem.push_attribute(("timestamp", s!(timestamp)));
+    elem.push_attribute(("version", "2.0.3"));
+    elem
+}
+
+/// `<package>` start tag with the rates of the package
+fn package_start(name: &str, summary: &Summary) -> BytesStart<'static> {
+    let mut pkg = BytesStart::new("package");
+    pkg.push_attribute(("line-rate", s!(summary.line_rate())));
+    pkg.push_attribute(("branch-rate", s!(summary.branch_rate())));
+    pkg.push_attribute(("name", name));
+    pkg.push_attribute(("complexity", "0"));
+    pkg
+}
+
+/// Writes one `<class>` element, `root` is the source root of the class if there are several
+fn write_class<D: Demangler, W: Write>(
+    writer: &mut Writer<W>,
+    class_name: &str,
+    root: Option<&str>,
+    cd: &Class,
+    demangler: &mut D,
+    options: &XmlOptions,
+) -> anyhow::Result<()> {
+    let grouped = cd.grouped_methods(options.method_grouping, demangler)?;
+    let methods = grouped.as_ref().map_or(&cd.methods, |(methods, _)| methods);
+    let mut class = BytesStart::new("class");
+    let cd_sum = cd.summary();
+    class.push_attribute(("branch-rate", s!(cd_sum.branch_rate())));
+    class.push_attribute(("complexity", "0"));
+    class.push_attribute(("filename", class_name));
+    class.push_attribute(("line-rate", s!(cd_sum.line_rate())));
+    class.push_attribute(("name", cd.name.as_str()));
+    if let Some(root) = root {
+        class.push_attribute(("source", root));
+    }
+    writer.write_event(Event::Start(class))?;
+    // methods
+    writer.write_event(Event::Start(BytesStart::new("methods")))?;
+
+    for (method_name, (line, hits)) in methods {
+        let mut method = BytesStart::new("method");
+        let line_rate = if *hits > 0 { 1. } else { 0. };
+        let branch_rate = if *hits > 0 { 1. } else { 0. };
+        let full_name = demangler.demangle(method_name.as_str())?;
+        let (name, signature) = options.method_names.apply(&full_name);
+        method.push_attribute(("name", name));
+        method.push_attribute(("signature", signature));
+        method.push_attribute(("complexity", "0"));
+        method.push_attribute(("line-rate", s!(line_rate)));
+        method.push_attribute(("branch-rate", s!(branch_rate)));
+        writer.write_event(Event::Start(method))?;
+        // Method lines (always exactly one?)
+        writer.write_event(Event::Start(BytesStart::new("lines")))?;
+
+        writer
+            .create_element("line")
+            .with_attributes([
+                ("hits", s!(hits)),
+                ("number", s!(line)),
+                ("branch", "false"),
+            ])
+            .write_empty()?;
+
+        // close method lines
+        writer.write_event(Event::End(BytesEnd::new("lines")))?;
+
+        // close methods
+        writer.write_event(Event::End(BytesEnd::new("method")))?;
+    }
+    writer.write_event(Event::End(BytesEnd::new("methods")))?;
+    // add class lines
+    writer.write_event(Event::Start(BytesStart::new("lines")))?;
+    let mut line_keys: Vec<usize> = cd.lines.keys().copied().collect();
+    line_keys.sort_unstable();
+    for line_number in &line_keys {
+        #[allow(clippy::unwrap_used, reason = "guaranteed, using sorted keys as input")]
+        let cd_line = cd.lines.get(line_number).unwrap();
+        let branch = cd_line.branch.to_string();
+        let hits = cd_line.hits.to_string();
+        let number = line_number.to_string();
+        let cond_cov;
+        let mut attrs = vec![
+            ("branch", branch.as_str()),
+            ("hits", hits.as_str()),
+            ("number", number.as_str()),
+        ];
+        if cd_line.branch {
+            let total = cd_line.branches_total();
+            let covered = cd_line.branches_covered();
+            let percentage = covered * 100 / total;
+            cond_cov = format!("{percentage}% ({covered}/{total})");
+            attrs.push(("condition-coverage", cond_cov.as_str()));
+        }
+        writer
+            .create_element("line")
+            .with_attributes(attrs)
+            .write_empty()?;
+
+        // close class lines
+    }
+    writer.write_event(Event::End(BytesEnd::new("lines")))?;
+    // close class
+    writer.write_event(Event::End(BytesEnd::new("class")))?;
+    Ok(())
+}
+
+/// Options of the written XML, see [`dump_xml_with`]
+#[derive(Debug, Default, Clone)]
+pub struct XmlOptions {
+    /// how demangled names are split into the `name` and `signature` of a `<method>`
+    pub method_names: MethodNames,
+    /// which functions are merged into one `<method>`, see [`CoverageData::group_methods`]
+    pub method_grouping: MethodGrouping,
+}
+
 /// Dumps cobertura XML into given Writer object
 ///
 /// # Errors
@@ -483,123 +969,59 @@ macro_rules! s {
 /// # Panics
 ///
 /// unlikely
-pub fn dump_xml<D: for<'a> Demangler<'a, 'a>, W: Write>(
+pub fn dump_xml<D: Demangler, W: Write>(
+    writer: W,
+    cov_data: &CoverageData,
+    timestamp: u64,
+    demangler: D,
+) -> anyhow::Result<W> {
+    dump_xml_with(
+        writer,
+        cov_data,
+        timestamp,
+        demangler,
+        &XmlOptions::default(),
+    )
+}
+
+/// Dumps cobertura XML into given Writer object with [`XmlOptions`]
+///
+/// # Errors
+///
+/// IO Error
+pub fn dump_xml_with<D: Demangler, W: Write>(
     writer: W,
     cov_data: &CoverageData,
     timestamp: u64,
     mut demangler: D,
+    options: &XmlOptions,
 ) -> anyhow::Result<W> {
     let mut writer = Writer::new_with_indent(writer, b' ', 4);
 
-    let mut elem = BytesStart::new("coverage");
-    let cdsummary = cov_data.summary();
-    elem.push_attribute(("branch-rate", s!(cdsummary.branch_rate())));
-    elem.push_attribute(("branches-covered", s!(cdsummary.branches_covered)));
-    elem.push_attribute(("branches-valid", s!(cdsummary.branches_total)));
-    elem.push_attribute(("complexity", "0"));
-    elem.push_attribute(("line-rate", s!(cdsummary.line_rate())));
-    elem.push_attribute(("lines-covered", s!(cdsummary.lines_covered)));
-    elem.push_attribute(("lines-valid", s!(cdsummary.lines_total)));
-    elem.push_attribute(("timestamp", s!(timestamp)));
-    elem.push_attribute(("version", "2.0.3"));
-    writer.write_event(Event::Start(elem))?;
+    writer.write_event(Event::Start(coverage_start(&cov_data.summary(), timestamp)))?;
 
     // Sources
     writer.write_event(Event::Start(BytesStart::new("sources")))?;
 
-    writer.write_event(Event::Start(BytesStart::new("source")))?;
-    writer.write_event(Event::Text(BytesText::new(&cov_data.base_dir)))?;
-    writer.write_event(Event::End(BytesEnd::new("source")))?;
+    for source in cov_data.sources() {
+        writer.write_event(Event::Start(BytesStart::new("source")))?;
+        writer.write_event(Event::Text(BytesText::new(source)))?;
+        writer.write_event(Event::End(BytesEnd::new("source")))?;
+    }
 
     writer.write_event(Event::End(BytesEnd::new("sources")))?;
     // packages
     writer.write_event(Event::Start(BytesStart::new("packages")))?;
+    demangler.prepare(&cov_data.method_names())?;
 
     for (pkg_name, package) in &cov_data.packages {
-        let mut pkg = BytesStart::new("package");
-        let pkg_sum = package.summary();
-        pkg.push_attribute(("line-rate", s!(pkg_sum.line_rate())));
-        pkg.push_attribute(("branch-rate", s!(pkg_sum.branch_rate())));
-        pkg.push_attribute(("name", pkg_name.as_str()));
-        pkg.push_attribute(("complexity", "0"));
-        writer.write_event(Event::Start(pkg))?;
+        writer.write_event(Event::Start(package_start(pkg_name, &package.summary())))?;
         // classes
         writer.write_event(Event::Start(BytesStart::new("classes")))?;
 
-        for (class_name, cd) in &package.classes {
-            let mut class = BytesStart::new("class");
-            let cd_sum = cd.summary();
-            class.push_attribute(("branch-rate", s!(cd_sum.branch_rate())));
-            class.push_attribute(("complexity", "0"));
-            class.push_attribute(("filename", class_name.as_str()));
-            class.push_attribute(("line-rate", s!(cd_sum.line_rate())));
-            class.push_attribute(("name", cd.name.as_str()));
-            writer.write_event(Event::Start(class))?;
-            // methods
-            writer.write_event(Event::Start(BytesStart::new("methods")))?;
-
-            for (method_name, (line, hits)) in &cd.methods {
-                let mut method = BytesStart::new("method");
-                let line_rate = if *hits > 0 { 1. } else { 0. };
-                let branch_rate = if *hits > 0 { 1. } else { 0. };
-                method.push_attribute(("name", demangler.demangle(method_name.as_str())?.as_ref()));
-                method.push_attribute(("signature", ""));
-                method.push_attribute(("complexity", "0"));
-                method.push_attribute(("line-rate", s!(line_rate)));
-                method.push_attribute(("branch-rate", s!(branch_rate)));
-                writer.write_event(Event::Start(method))?;
-                // Method lines (always exactly one?)
-                writer.write_event(Event::Start(BytesStart::new("lines")))?;
-
-                writer
-                    .create_element("line")
-                    .with_attributes([
-                        ("hits", s!(hits)),
-                        ("number", s!(line)),
-                        ("branch", "false"),
-                    ])
-                    .write_empty()?;
-
-                // close method lines
-                writer.write_event(Event::End(BytesEnd::new("lines")))?;
-
-                // close methods
-                writer.write_event(Event::End(BytesEnd::new("method")))?;
-            }
-            writer.write_event(Event::End(BytesEnd::new("methods")))?;
-            // add class lines
-            writer.write_event(Event::Start(BytesStart::new("lines")))?;
-            let mut line_keys: Vec<usize> = cd.lines.keys().copied().collect();
-            line_keys.sort_unstable();
-            for line_number in &line_keys {
-                #[allow(clippy::unwrap_used, reason = "guaranteed, using sorted keys as input")]
-                let cd_line = cd.lines.get(line_number).unwrap();
-                let branch = cd_line.branch.to_string();
-                let hits = cd_line.hits.to_string();
-                let number = line_number.to_string();
-                let cond_cov;
-                let mut attrs = vec![
-                    ("branch", branch.as_str()),
-                    ("hits", hits.as_str()),
-                    ("number", number.as_str()),
-                ];
-                if cd_line.branch {
-                    let total = cd_line.branches_total;
-                    let covered = cd_line.branches_covered;
-                    let percentage = covered * 100 / total;
-                    cond_cov = format!("{percentage}% ({covered}/{total})");
-                    attrs.push(("condition-coverage", cond_cov.as_str()));
-                }
-                writer
-                    .create_element("line")
-                    .with_attributes(attrs.into_iter())
-                    .write_empty()?;
-
-                // close class lines
-            }
-            writer.write_event(Event::End(BytesEnd::new("lines")))?;
-            // close class
-            writer.write_event(Event::End(BytesEnd::new("class")))?;
+        for ((_, class_name), cd) in &package.classes {
+            let root = cov_data.class_root(cd);
+            write_class(&mut writer, class_name, root, cd, &mut demangler, options)?;
         }
         writer.write_event(Event::End(BytesEnd::new("classes")))?;
         // close package
@@ -620,7 +1042,7 @@ pub fn dump_xml<D: for<'a> Demangler<'a, 'a>, W: Write>(
 ///
 /// - UTF-8 conversion
 /// - IO Error
-pub fn coverage_to_string<D: for<'a> Demangler<'a, 'a>>(
+pub fn coverage_to_string<D: Demangler>(
     cov_data: &CoverageData,
     timestamp: u64,
     demangler: D,
@@ -640,18 +1062,56 @@ pub fn coverage_to_string<D: for<'a> Demangler<'a, 'a>>(
 ///
 /// - UTF-8 conversion
 /// - IO Error
-pub fn coverage_to_file<P: AsRef<Path>, D: for<'a> Demangler<'a, 'a>>(
+pub fn coverage_to_file<P: AsRef<Path>, D: Demangler>(
     filename: P,
     cov_data: &CoverageData,
     timestamp: u64,
     demangler: D,
 ) -> anyhow::Result<()> {
-    let mut buffer = std::fs::File::create(filename)?;
-    buffer.write_all(XML_HEADER.as_bytes())?;
-    dump_xml(buffer, cov_data, timestamp, demangler)?;
+    coverage_to_file_with(
+        filename,
+        cov_data,
+        timestamp,
+        demangler,
+        &XmlOptions::default(),
+    )
+}
+
+/// Convenience function to write coverage data to an XML file with [`XmlOptions`]
+///
+/// # Errors
+///
+/// - UTF-8 conversion
+/// - IO Error
+pub fn coverage_to_file_with<P: AsRef<Path>, D: Demangler>(
+    filename: P,
+    cov_data: &CoverageData,
+    timestamp: u64,
+    demangler: D,
+    options: &XmlOptions,
+) -> anyhow::Result<()> {
+    let buffer = std::fs::File::create(filename)?;
+    coverage_to_writer_with(buffer, cov_data, timestamp, demangler, options)?;
     Ok(())
 }
 
+/// Writes coverage data as an XML document with [`XmlOptions`] into given Writer object, e.g.
+/// standard output
+///
+/// # Errors
+///
+/// IO Error
+pub fn coverage_to_writer_with<W: Write, D: Demangler>(
+    mut writer: W,
+    cov_data: &CoverageData,
+    timestamp: u64,
+    demangler: D,
+    options: &XmlOptions,
+) -> anyhow::Result<W> {
+    writer.write_all(XML_HEADER.as_bytes())?;
+    dump_xml_with(writer, cov_data, timestamp, demangler, options)
+}
+
 const XML_HEADER: &str = r#"<?xml version="1.0" ?>
 <!DOCTYPE coverage SYSTEM "https://cobertura.sourceforge.net/xml/coverage-04.dtd">
 "#;
@@ -0,0 +1,75 @@
+//! Strategies to derive the `<package name>` from a relative source path
+use regex::Regex;
+
+/// How the package of a source file is named, all strategies work on the relative path with
+/// `/` or `\` separators
+#[derive(Debug, Clone, Default)]
+pub enum PackageNaming {
+    /// every directory is a package with a dotted name like `src.foo.bar`
+    #[default]
+    Full,
+    /// only the first N directories, deeper files belong to their ancestor at that depth
+    Depth(usize),
+    /// the capture groups of the first match joined with `.`, paths not matching are named
+    /// like [`PackageNaming::Full`]
+    Regex(Regex),
+    /// path prefixes mapped to logical component names, the longest matching prefix wins,
+    /// paths not matching any prefix are named like [`PackageNaming::Full`]
+    Mapping(Vec<(String, String)>),
+}
+
+impl std::str::FromStr for PackageNaming {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "full" {
+            Ok(Self::Full)
+        } else if let Some(depth) = s.strip_prefix("depth:") {
+            Ok(Self::Depth(depth.parse()?))
+        } else if let Some(pattern) = s.strip_prefix("regex:") {
+            Ok(Self::Regex(Regex::new(pattern)?))
+        } else {
+            anyhow::bail!("unknown package naming {s:?}, expected full, depth:N or regex:PATTERN")
+        }
+    }
+}
+
+impl PackageNaming {
+    /// Package name of a relative source path
+    #[must_use]
+    pub fn package_name(&self, relative_file_name: &str) -> String {
+        let dirs = directories(relative_file_name);
+        match self {
+            Self::Full => dirs.join("."),
+            Self::Depth(depth) => dirs[..dirs.len().min(*depth)].join("."),
+            Self::Regex(re) => match re.captures(relative_file_name) {
+                Some(captures) => captures
+                    .iter()
+                    .skip(1)
+                    .flatten()
+                    .map(|m| m.as_str())
+                    .collect::<Vec<&str>>()
+                    .join("."),
+                None => dirs.join("."),
+            },
+            Self::Mapping(prefixes) => prefixes
+                .iter()
+                .filter(|(prefix, _)| {
+                    let prefix: Vec<&str> = prefix
+                        .split(['/', '\\'])
+                        .filter(|e| !e.is_empty())
+                        .collect();
+                    dirs.starts_with(&prefix)
+                })
+                .max_by_key(|(prefix, _)| prefix.len())
+                .map_or_else(|| dirs.join("."), |(_, name)| name.clone()),
+        }
+    }
+}
+
+/// Directory components of a relative path, without the file name
+fn directories(relative_file_name: &str) -> Vec<&str> {
+    let mut elems: Vec<&str> = relative_file_name.split(['/', '\\']).collect();
+    elems.pop();
+    elems
+}
@@ -0,0 +1,72 @@
+//! Parses many tracefiles and demangles names on a thread pool
+use std::collections::HashMap;
+use std::path::Path;
+
+use rayon::prelude::*;
+
+use crate::{parse_file_with, CoverageData, Demangler, ParseOptions};
+
+/// Parses every file on its own thread of a pool with `jobs` threads (all cores if `None`) and
+/// merges the results with [`CoverageData::merge`]
+///
+/// # Errors
+///
+/// Thread pool creation, IO or parsing errors of any file.
+pub fn parse_files_parallel<P: AsRef<Path> + Sync>(
+    files: &[P],
+    base_dir: &Path,
+    excludes: &[&str],
+    options: &ParseOptions,
+    jobs: Option<usize>,
+) -> anyhow::Result<CoverageData> {
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(jobs.unwrap_or(0))
+        .build()?;
+    pool.install(|| {
+        files
+            .par_iter()
+            .map(|file| parse_file_with(file.as_ref(), base_dir, excludes, options))
+            .try_reduce(CoverageData::default, |mut merged, cov_data| {
+                merged.merge(cov_data);
+                Ok(merged)
+            })
+    })
+}
+
+/// Demangles `names` on a pool with `jobs` threads (all cores if `None`), every thread gets its
+/// own demangler from `new_demangler` and one chunk of the names. The result is meant for
+/// [`crate::CachingDemangler::with_names`].
+///
+/// # Errors
+///
+/// Thread pool creation or errors of the demanglers.
+pub fn demangle_parallel<D, F>(
+    names: &[&str],
+    new_demangler: F,
+    jobs: Option<usize>,
+) -> anyhow::Result<HashMap<String, String>>
+where
+    D: Demangler,
+    F: Fn() -> std::io::Result<D> + Sync,
+{
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(jobs.unwrap_or(0))
+        .build()?;
+    let chunk_size = names.len().div_ceil(pool.current_num_threads()).max(1);
+    let chunks: Vec<Vec<(String, String)>> = pool.install(|| {
+        names
+            .par_chunks(chunk_size)
+            .map(|chunk| {
+                let mut demangler = new_demangler()?;
+                demangler.prepare(chunk)?;
+                let pairs = chunk
+                    .iter()
+                    .map(|name| Ok(((*name).to_string(), demangler.demangle(name)?.into_owned())))
+                    .collect::<std::io::Result<Vec<_>>>()?;
+                demangler.stop()?;
+                Ok(pairs)
+            })
+            .collect::<std::io::Result<_>>()
+    })?;
+    Ok(chunks.into_iter().flatten().collect())
+}
@@ -0,0 +1,259 @@
+//! Rewrites of `SF` source paths, e.g. from build container paths to the checkout layout, and
+//! their normalization relative to `base_dir`
+use std::borrow::Cow;
+use std::path::{Component, Path, PathBuf};
+
+use regex::Regex;
+
+/// A `FROM=TO` rewrite rule for source paths, applied before package and class naming.
+///
+/// `FROM=TO` replaces the leading path components `FROM` with `TO`, `regex:FROM=TO` replaces
+/// the first match of the regular expression `FROM` with `TO`, which may use `$1` or `${name}`
+/// capture group references.
+#[derive(Debug, Clone)]
+pub enum PathMap {
+    /// replaces a leading path, matched on whole components
+    Prefix {
+        /// path prefix to replace
+        from: String,
+        /// replacement, an empty string makes the rest relative
+        to: String,
+    },
+    /// replaces the first match of a regular expression
+    Regex {
+        /// pattern to replace
+        from: Regex,
+        /// replacement with optional capture group references
+        to: String,
+    },
+}
+
+impl std::str::FromStr for PathMap {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (from, to) = s
+            .split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("path map {s:?} is not of the form FROM=TO"))?;
+        if let Some(pattern) = from.strip_prefix("regex:") {
+            Ok(Self::Regex {
+                from: Regex::new(pattern)?,
+                to: to.to_string(),
+            })
+        } else if from.is_empty() {
+            anyhow::bail!("path map {s:?} has an empty FROM prefix")
+        } else {
+            Ok(Self::Prefix {
+                from: from.to_string(),
+                to: to.to_string(),
+            })
+        }
+    }
+}
+
+impl PathMap {
+    /// Rewritten path, `None` if the rule does not match
+    #[must_use]
+    pub fn apply(&self, path: &str) -> Option<String> {
+        match self {
+            Self::Prefix { from, to } => {
+                let rest = Path::new(path).strip_prefix(from).ok()?;
+                Path::new(to).join(rest).to_str().map(str::to_string)
+            }
+            Self::Regex { from, to } => from
+                .is_match(path)
+                .then(|| from.replace(path, to.as_str()).into_owned()),
+        }
+    }
+}
+
+/// Applies the first matching rule, the path is kept if none matches
+pub(crate) fn map_path<'p>(maps: &[PathMap], path: &'p str) -> Cow<'p, str> {
+    maps.iter()
+        .find_map(|map| map.apply(path))
+        .map_or(Cow::Borrowed(path), Cow::Owned)
+}
+
+/// Separator style of the `filename` attribute
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PathStyle {
+    /// separator of the platform running the conversion
+    #[default]
+    Native,
+    /// always `/`
+    Unix,
+    /// always `\`
+    Windows,
+}
+
+impl std::str::FromStr for PathStyle {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "native" => Ok(Self::Native),
+            "unix" | "slash" => Ok(Self::Unix),
+            "windows" | "backslash" => Ok(Self::Windows),
+            _ => anyhow::bail!("unknown path style {s:?}, expected native, unix or windows"),
+        }
+    }
+}
+
+impl PathStyle {
+    pub(crate) fn apply(self, path: &mut String) {
+        let (from, to) = match self {
+            Self::Native => return,
+            Self::Unix => ('\\', "/"),
+            Self::Windows => ('/', "\\"),
+        };
+        if path.contains(from) {
+            *path = path.replace(from, to);
+        }
+    }
+}
+
+/// Whether the path starts with a Windows drive letter like `C:`
+fn has_drive_letter(path: &str) -> bool {
+    matches!(path.as_bytes(), [letter, b':', ..] if letter.is_ascii_alphabetic())
+}
+
+/// Converts Windows paths from tracefiles recorded on Windows to `/` separators and an upper
+/// case drive letter, so they can be handled by [`Path`] on other platforms
+pub(crate) fn portable(path: &str) -> Cow<'_, str> {
+    if cfg!(windows) || !(path.contains('\\') || has_drive_letter(path)) {
+        return Cow::Borrowed(path);
+    }
+    let mut path = path.replace('\\', "/");
+    if has_drive_letter(&path) {
+        path[..1].make_ascii_uppercase();
+    }
+    Cow::Owned(path)
+}
+
+/// Removes `.` components and resolves `..` against preceding components without touching the
+/// filesystem, duplicate separators are dropped by [`Path::components`]
+pub(crate) fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    normalized.pop();
+                }
+                // `/..` is `/`
+                Some(Component::RootDir | Component::Prefix(_)) => (),
+                _ => normalized.push(".."),
+            },
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// Relative path from the directory `base` to `path` like Python's `os.path.relpath`, both have
+/// to be normalized and either both absolute or both relative. `None` if there is no relative
+/// path, e.g. on different Windows drives.
+pub(crate) fn relative_to(path: &Path, base: &Path) -> Option<PathBuf> {
+    if path.has_root() != base.has_root() {
+        return None;
+    }
+    let mut path_components = path.components().peekable();
+    let mut base_components = base.components().peekable();
+    while let (Some(p), Some(b)) = (path_components.peek(), base_components.peek()) {
+        if p != b {
+            break;
+        }
+        path_components.next();
+        base_components.next();
+    }
+    let mut relative = PathBuf::new();
+    for component in base_components {
+        match component {
+            Component::Normal(_) => relative.push(".."),
+            // different drive or `base` leaving the common part via `..`
+            _ => return None,
+        }
+    }
+    relative.extend(path_components);
+    Some(relative)
+}
+
+/// Whether both paths start with the same directory below the file system root
+fn share_directory(path: &Path, root: &Path) -> bool {
+    path.components()
+        .zip(root.components())
+        .take_while(|(p, r)| p == r)
+        .any(|(p, _)| matches!(p, Component::Normal(_)))
+}
+
+/// Computes `SF` paths relative to the longest matching source root, relative paths are
+/// resolved against the current directory like `os.path.relpath` does
+pub(crate) struct PathResolver {
+    cwd: PathBuf,
+    roots: Vec<PathBuf>,
+    canonicalize: bool,
+}
+
+impl PathResolver {
+    /// `roots` starts with `base_dir`, which is used for files outside of all roots
+    pub(crate) fn new<'r>(roots: impl IntoIterator<Item = &'r str>, canonicalize: bool) -> Self {
+        let mut resolver = Self {
+            // without a current directory relative paths stay relative
+            cwd: std::env::current_dir().unwrap_or_default(),
+            roots: vec![],
+            canonicalize,
+        };
+        resolver.roots = roots
+            .into_iter()
+            .map(|root| resolver.absolute(Path::new(portable(root).as_ref())))
+            .collect();
+        resolver
+    }
+
+    /// Normalized absolute path, symlinks resolved if requested and the file exists. Paths
+    /// with a drive letter are absolute, even on platforms without drives.
+    fn absolute(&self, path: &Path) -> PathBuf {
+        let path = if path.to_str().is_some_and(has_drive_letter) {
+            normalize(path)
+        } else {
+            normalize(&self.cwd.join(path))
+        };
+        if self.canonicalize {
+            std::fs::canonicalize(&path).unwrap_or(path)
+        } else {
+            path
+        }
+    }
+
+    /// Path relative to the longest root containing it and the index of that root. Paths
+    /// outside of all roots are relative to the first one and flagged as outside. Paths
+    /// sharing no directory with the first root, like `/usr/include/...` for a checkout in
+    /// `/home`, and paths without a relative spelling stay absolute, so their name does not
+    /// depend on the current directory.
+    pub(crate) fn relative(&self, path: &str) -> (PathBuf, usize, bool) {
+        let path = self.absolute(Path::new(path));
+        let inside = self
+            .roots
+            .iter()
+            .enumerate()
+            .filter(|(_, root)| path.starts_with(root))
+            .max_by_key(|(_, root)| root.components().count());
+        if let Some((index, root)) = inside {
+            if let Some(relative) = relative_to(&path, root) {
+                return (relative, index, false);
+            }
+        }
+        let first = self.roots.first();
+        match first
+            .filter(|root| share_directory(&path, root))
+            .and_then(|root| relative_to(&path, root))
+        {
+            Some(relative) => {
+                let outside = relative.components().next() == Some(Component::ParentDir);
+                (relative, 0, outside)
+            }
+            None => (path, 0, true),
+        }
+    }
+}
@@ -0,0 +1,163 @@
+//! User-defined regex rewrites of demangled method names, applied after any demangler
+use std::borrow::Cow;
+use std::io;
+
+use regex::Regex;
+
+use crate::Demangler;
+
+/// A `PATTERN=REPLACEMENT` rewrite of method names, every match of the regular expression
+/// `PATTERN` is replaced by `REPLACEMENT`, which may use `$1` or `${name}` capture group
+/// references. The first `=` separates both, write `\x3D` for a `=` in the pattern.
+#[derive(Debug, Clone)]
+pub struct RewriteRule {
+    /// pattern to replace
+    pub pattern: Regex,
+    /// replacement with optional capture group references
+    pub replacement: String,
+}
+
+impl std::str::FromStr for RewriteRule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (pattern, replacement) = s.split_once('=').ok_or_else(|| {
+            anyhow::anyhow!("rewrite rule {s:?} is not of the form PATTERN=REPLACEMENT")
+        })?;
+        Self::new(pattern, replacement)
+    }
+}
+
+impl RewriteRule {
+    /// Replaces matches of `pattern` with `replacement`
+    /// # Errors
+    /// Invalid or empty regex
+    pub fn new(pattern: &str, replacement: &str) -> anyhow::Result<Self> {
+        anyhow::ensure!(!pattern.is_empty(), "rewrite rule with an empty pattern");
+        Ok(Self {
+            pattern: Regex::new(pattern)?,
+            replacement: replacement.to_string(),
+        })
+    }
+
+    /// Rewritten name, `None` if the pattern does not match
+    #[must_use]
+    pub fn apply(&self, name: &str) -> Option<String> {
+        match self.pattern.replace_all(name, self.replacement.as_str()) {
+            Cow::Borrowed(_) => None,
+            Cow::Owned(name) => Some(name),
+        }
+    }
+}
+
+/// Built-in sets of [`RewriteRule`]s
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RewritePreset {
+    /// `rust`: drops `::h<hash>` suffixes of legacy symbols, closures like `::{{closure}}` and
+    /// `::{closure#0}` and turbofish generics, collapses the remaining generic parameters to `<_>`
+    Rust,
+    /// `cpp`: strips the inline namespaces `std::__1::` of libc++ and `std::__cxx11::` of
+    /// libstdc++, shortens `std::basic_string<char, ...>` to `std::string` and drops default
+    /// `std::allocator` arguments
+    Cpp,
+}
+
+impl std::str::FromStr for RewritePreset {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "rust" => Ok(Self::Rust),
+            "cpp" | "c++" => Ok(Self::Cpp),
+            _ => anyhow::bail!("unknown rewrite preset {s:?}, expected rust or cpp"),
+        }
+    }
+}
+
+/// `<...>` with up to five levels of nested angle brackets
+fn generics_pattern() -> String {
+    let mut generics = String::from("<[^<>]*>");
+    for _ in 0..4 {
+        generics = format!("<(?:[^<>]|{generics})*>");
+    }
+    generics
+}
+
+impl RewritePreset {
+    /// The rules of the preset, in the order they are applied
+    /// # Panics
+    /// When a built-in regular expression is invalid.
+    #[allow(
+        clippy::unwrap_used,
+        reason = "built-in regexes are known to compile fine, no panic"
+    )]
+    #[must_use]
+    pub fn rules(self) -> Vec<RewriteRule> {
+        let generics = generics_pattern();
+        let rules: Vec<(String, &str)> = match self {
+            Self::Rust => vec![
+                (r"::h[0-9a-f]{16}$".to_string(), ""),
+                (r"::\{\{closure\}\}|::\{closure#\d+\}".to_string(), ""),
+                (format!("::{generics}"), ""),
+                (format!(r"(\w){generics}"), "${1}<_>"),
+            ],
+            Self::Cpp => vec![
+                (r"\bstd::__(?:1|cxx11)::".to_string(), "std::"),
+                (
+                    r"\bstd::basic_string<char, std::char_traits<char>, std::allocator<char> ?>"
+                        .to_string(),
+                    "std::string",
+                ),
+                (
+                    r"\bstd::basic_string<wchar_t, std::char_traits<wchar_t>, std::allocator<wchar_t> ?>"
+                        .to_string(),
+                    "std::wstring",
+                ),
+                (format!(", std::allocator{generics} ?>"), ">"),
+            ],
+        };
+        rules
+            .into_iter()
+            .map(|(pattern, replacement)| RewriteRule::new(&pattern, replacement).unwrap())
+            .collect()
+    }
+}
+
+/// Applies [`RewriteRule`]s to the names of another demangler, every rule in order to the result
+/// of the previous one
+pub struct RewritingDemangler<D> {
+    inner: D,
+    rules: Vec<RewriteRule>,
+}
+
+impl<D: Demangler> RewritingDemangler<D> {
+    /// wraps `inner`, without rules names are passed through unchanged
+    #[must_use]
+    pub fn new(inner: D, rules: Vec<RewriteRule>) -> Self {
+        Self { inner, rules }
+    }
+}
+
+impl<D: Demangler> Demangler for RewritingDemangler<D> {
+    fn demangle<'a>(&mut self, ident: &'a str) -> io::Result<Cow<'a, str>> {
+        let mut name = self.inner.demangle(ident)?;
+        for rule in &self.rules {
+            if let Some(rewritten) = rule.apply(&name) {
+                name = Cow::Owned(rewritten);
+            }
+        }
+        Ok(name)
+    }
+
+    fn prepare(&mut self, idents: &[&str]) -> io::Result<()> {
+        self.inner.prepare(idents)
+    }
+
+    fn stop(&mut self) -> io::Result<()> {
+        self.inner.stop()
+    }
+
+    fn take_diagnostics(&mut self) -> Vec<String> {
+        self.inner.take_diagnostics()
+    }
+}
@@ -0,0 +1,105 @@
+//! Splits demangled function names into the method name and its signature
+/// What the `name` and `signature` attributes of a `<method>` hold
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MethodNames {
+    /// the whole demangled name in `name`, `signature` stays empty
+    #[default]
+    Full,
+    /// only the name without return type, parameters and qualifiers, e.g. `Foo::bar`
+    Short,
+    /// the short name in `name`, parameters and qualifiers like `(int) const` in `signature`
+    Split,
+}
+
+impl std::str::FromStr for MethodNames {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "full" => Ok(Self::Full),
+            "short" => Ok(Self::Short),
+            "split" => Ok(Self::Split),
+            _ => anyhow::bail!("unknown method names {s:?}, expected full, short or split"),
+        }
+    }
+}
+
+impl MethodNames {
+    /// `name` and `signature` attribute of a demangled name
+    #[must_use]
+    pub fn apply(self, demangled: &str) -> (&str, &str) {
+        match self {
+            Self::Full => (demangled, ""),
+            Self::Short => (split_signature(demangled).0, ""),
+            Self::Split => split_signature(demangled),
+        }
+    }
+}
+
+/// Splits a demangled C++ name like `void ns::Foo::bar<int>(int, char const*) const` into the
+/// name `ns::Foo::bar<int>` and the signature `(int, char const*) const`. The return type is
+/// dropped, except for a trailing Swift return type like `-> Swift.Int`, which stays in the
+/// signature. Names without a parameter list, like those of Rust, are returned with an empty
+/// signature.
+#[must_use]
+pub fn split_signature(demangled: &str) -> (&str, &str) {
+    let bytes = demangled.as_bytes();
+    let mut depth = 0usize;
+    // start of the name after a return type
+    let mut name_start = 0;
+    // the last parameter list at depth 0
+    let mut params = None;
+    // spaces of `operator new` or conversion operators don't end a return type
+    let mut in_operator = false;
+    let mut i = 0;
+    while i < bytes.len() {
+        if depth == 0 && is_operator_keyword(demangled, i) {
+            i += "operator".len();
+            match bytes.get(i..i + 2) {
+                Some(b"()" | b"[]") => i += 2,
+                _ => {
+                    while i < bytes.len() && b"<>=!+-*/%^&|~,".contains(&bytes[i]) {
+                        i += 1;
+                    }
+                }
+            }
+            in_operator = true;
+            continue;
+        }
+        match bytes[i] {
+            b'(' => {
+                if depth == 0 && i > name_start {
+                    params = Some(i);
+                    in_operator = false;
+                }
+                depth += 1;
+            }
+            // Swift function types and return types, `main.f(Swift.Int) -> ()`
+            b'-' if bytes.get(i + 1) == Some(&b'>') => {
+                if depth == 0 && params.is_some() {
+                    break;
+                }
+                i += 1;
+            }
+            b'<' | b'[' | b'{' => depth += 1,
+            b')' | b'>' | b']' | b'}' => depth = depth.saturating_sub(1),
+            b' ' if depth == 0 && params.is_none() && !in_operator => name_start = i + 1,
+            _ => (),
+        }
+        i += 1;
+    }
+    match params {
+        Some(params) if depth == 0 => (&demangled[name_start..params], &demangled[params..]),
+        _ => (demangled, ""),
+    }
+}
+
+/// whether the keyword `operator` starts at byte `i` of `name`
+fn is_operator_keyword(name: &str, i: usize) -> bool {
+    let bytes = name.as_bytes();
+    bytes[i..].starts_with(b"operator")
+        && (i == 0 || !(bytes[i - 1].is_ascii_alphanumeric() || bytes[i - 1] == b'_'))
+        && !bytes
+            .get(i + "operator".len())
+            .is_some_and(|c| c.is_ascii_alphanumeric() || *c == b'_')
+}
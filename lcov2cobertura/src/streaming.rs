@@ -0,0 +1,296 @@
+//! Bounded-memory conversion, every class is written as soon as its record ends
+use std::collections::HashSet;
+use std::io::{BufRead, Lines, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use quick_xml::events::{BytesEnd, BytesStart, BytesText, Event};
+use quick_xml::writer::Writer;
+
+use crate::{
+    coverage_start, package_start, parse_records, write_class, ClassKey, CompSummary, CoverageData,
+    Demangler, ParseOptions, Summary, XmlOptions, XML_HEADER,
+};
+
+/// spaces reserved in start tags whose attributes are only known at the end, fits the longest
+/// possible counts and rates
+const RESERVED: usize = 200;
+
+/// Result of a streamed conversion, the XML itself is only in the writer
+#[derive(Debug, Default)]
+pub struct StreamStats {
+    /// totals over all written classes
+    pub summary: Summary,
+    /// number of records skipped by the filters of [`ParseOptions`]
+    pub filtered_records: usize,
+}
+
+/// Start tag written with placeholder attributes and overwritten once the real values are known
+struct PatchableTag {
+    position: u64,
+    len: usize,
+}
+
+impl PatchableTag {
+    fn write<W: Write + Seek>(
+        writer: &mut Writer<W>,
+        start: &BytesStart<'_>,
+    ) -> anyhow::Result<Self> {
+        let mut content = String::from_utf8(start.to_vec())?;
+        content.push_str(&" ".repeat(RESERVED));
+        let len = content.len();
+        writer.write_event(Event::Start(BytesStart::from_content(
+            content,
+            start.name().as_ref().len(),
+        )))?;
+        // `<` and `>` around the content
+        let position = writer.get_mut().stream_position()? - len as u64 - 2;
+        Ok(Self { position, len })
+    }
+
+    fn patch<W: Write + Seek>(
+        &self,
+        writer: &mut Writer<W>,
+        start: &BytesStart<'_>,
+    ) -> anyhow::Result<()> {
+        let content = start.to_vec();
+        anyhow::ensure!(
+            content.len() <= self.len,
+            "attributes exceed the reserved space of the start tag"
+        );
+        let inner = writer.get_mut();
+        inner.seek(SeekFrom::Start(self.position))?;
+        inner.write_all(b"<")?;
+        inner.write_all(&content)?;
+        inner.write_all(" ".repeat(self.len - content.len()).as_bytes())?;
+        inner.write_all(b">")?;
+        inner.seek(SeekFrom::End(0))?;
+        Ok(())
+    }
+}
+
+/// package currently open in the output
+struct OpenPackage {
+    name: String,
+    tag: PatchableTag,
+    summary: Summary,
+}
+
+/// writes the classes and packages of the parsed records while parsing
+struct StreamWriter<W: Write + Seek, D> {
+    writer: Writer<W>,
+    demangler: D,
+    xml_options: XmlOptions,
+    excludes: Vec<regex::Regex>,
+    package: Option<OpenPackage>,
+    /// packages and classes written so far, input repeating them can't be streamed
+    closed_packages: HashSet<String>,
+    written_classes: HashSet<ClassKey>,
+    summary: Summary,
+}
+
+impl<W: Write + Seek, D: Demangler> StreamWriter<W, D> {
+    /// moves the finished class out of `cov_data` and writes it
+    fn record_end(
+        &mut self,
+        cov_data: &mut CoverageData,
+        package_name: &str,
+        class_key: &ClassKey,
+    ) -> anyhow::Result<()> {
+        let Some(package) = cov_data.packages.get_mut(package_name) else {
+            return Ok(());
+        };
+        let class = package.classes.remove(class_key);
+        if package.classes.is_empty() {
+            cov_data.packages.remove(package_name);
+        }
+        let Some(mut class) = class else {
+            return Ok(());
+        };
+        if self.excludes.iter().any(|re| re.is_match(package_name)) {
+            return Ok(());
+        }
+        anyhow::ensure!(
+            self.written_classes.insert(class_key.clone()),
+            "{} appears in several records, e.g. of several tests (`TN:`), streaming needs one \
+             record per source file, convert without streaming to merge them",
+            cov_data.class_path(class_key)
+        );
+        if self.package.as_ref().map(|p| p.name.as_str()) != Some(package_name) {
+            anyhow::ensure!(
+                !self.closed_packages.contains(package_name),
+                "package `{package_name}` continues after records of other packages, streaming \
+                 needs the records grouped by package and source file, convert without \
+                 streaming instead"
+            );
+            self.close_package()?;
+            let tag = PatchableTag::write(
+                &mut self.writer,
+                &package_start(package_name, &Summary::default()),
+            )?;
+            self.writer
+                .write_event(Event::Start(BytesStart::new("classes")))?;
+            self.package = Some(OpenPackage {
+                name: package_name.to_string(),
+                tag,
+                summary: Summary::default(),
+            });
+        }
+        let method_names: Vec<&str> = class.methods.keys().map(String::as_str).collect();
+        self.demangler.prepare(&method_names)?;
+        // grouped before the summary, so merged functions are counted once
+        class.group_methods(self.xml_options.method_grouping, &mut self.demangler)?;
+        write_class(
+            &mut self.writer,
+            &class_key.1,
+            cov_data.class_root(&class),
+            &class,
+            &mut self.demangler,
+            &self.xml_options,
+        )?;
+        let class_summary = class.summary();
+        if let Some(package) = &mut self.package {
+            package.summary = std::mem::take(&mut package.summary) + class_summary.clone();
+        }
+        self.summary = std::mem::take(&mut self.summary) + class_summary;
+        Ok(())
+    }
+
+    fn close_package(&mut self) -> anyhow::Result<()> {
+        if let Some(package) = self.package.take() {
+            self.closed_packages.insert(package.name.clone());
+            self.writer
+                .write_event(Event::End(BytesEnd::new("classes")))?;
+            self.writer
+                .write_event(Event::End(BytesEnd::new("package")))?;
+            package.tag.patch(
+                &mut self.writer,
+                &package_start(&package.name, &package.summary),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Converts LCOV lines to cobertura XML without keeping the whole report in memory: every
+/// class is written when its `end_of_record` is read and a package when the next record
+/// belongs to another one, memory scales with the largest source file. The totals of
+/// `<coverage>` and the rates of `<package>` are written last by seeking back into `writer`.
+///
+/// Records have to be grouped by source file and package, like `lcov` and `llvm-cov` write
+/// them. A file appearing in several records, e.g. of several tests, or a package continuing
+/// after another one is an error, as they can't be merged anymore. The names of the written
+/// files are kept to notice that.
+///
+/// # Errors
+///
+/// IO errors, invalid globs or regexes, errors parsing the lines, records not grouped by
+/// package and source file.
+#[allow(
+    clippy::too_many_arguments,
+    reason = "the arguments of parse_lines_with and dump_xml_with"
+)]
+pub fn stream_xml<P: AsRef<Path>, B: BufRead, W: Write + Seek, D: Demangler>(
+    lines: Lines<B>,
+    writer: W,
+    base_dir: P,
+    excludes: &[&str],
+    options: &ParseOptions,
+    timestamp: u64,
+    demangler: D,
+    xml_options: &XmlOptions,
+) -> anyhow::Result<(W, StreamStats)> {
+    let mut writer = Writer::new_with_indent(writer, b' ', 4);
+    let coverage_tag =
+        PatchableTag::write(&mut writer, &coverage_start(&Summary::default(), timestamp))?;
+    writer.write_event(Event::Start(BytesStart::new("sources")))?;
+    let base_dir_name = base_dir
+        .as_ref()
+        .to_str()
+        .ok_or_else(|| anyhow::anyhow!("base_dir cannot be converted to string"))?;
+    for source in
+        std::iter::once(base_dir_name).chain(options.source_roots.iter().map(String::as_str))
+    {
+        writer.write_event(Event::Start(BytesStart::new("source")))?;
+        writer.write_event(Event::Text(BytesText::new(source)))?;
+        writer.write_event(Event::End(BytesEnd::new("source")))?;
+    }
+    writer.write_event(Event::End(BytesEnd::new("sources")))?;
+    writer.write_event(Event::Start(BytesStart::new("packages")))?;
+
+    let excludes: Result<Vec<regex::Regex>, _> =
+        excludes.iter().map(|v| regex::Regex::new(v)).collect();
+    let mut stream = StreamWriter {
+        writer,
+        demangler,
+        xml_options: xml_options.clone(),
+        excludes: excludes?,
+        package: None,
+        closed_packages: HashSet::new(),
+        written_classes: HashSet::new(),
+        summary: Summary::default(),
+    };
+    let mut rest = parse_records(
+        lines,
+        base_dir.as_ref(),
+        options,
+        |cov_data, package, file| stream.record_end(cov_data, package, file),
+    )?;
+    // a last record without `end_of_record`
+    let unfinished: Vec<(String, ClassKey)> = rest
+        .packages
+        .iter()
+        .flat_map(|(package, p)| p.classes.keys().map(|file| (package.clone(), file.clone())))
+        .collect();
+    for (package, file) in unfinished {
+        stream.record_end(&mut rest, &package, &file)?;
+    }
+    stream.close_package()?;
+
+    let mut writer = stream.writer;
+    writer.write_event(Event::End(BytesEnd::new("packages")))?;
+    writer.write_event(Event::End(BytesEnd::new("coverage")))?;
+    coverage_tag.patch(&mut writer, &coverage_start(&stream.summary, timestamp))?;
+    stream.demangler.stop()?;
+    Ok((
+        writer.into_inner(),
+        StreamStats {
+            summary: stream.summary,
+            filtered_records: rest.filtered_records,
+        },
+    ))
+}
+
+/// Convenience function to stream LCOV lines into an XML file, see [`stream_xml`]
+///
+/// # Errors
+///
+/// IO errors, invalid globs or regexes, errors parsing the lines.
+#[allow(
+    clippy::too_many_arguments,
+    reason = "the arguments of parse_lines_with and dump_xml_with"
+)]
+pub fn stream_to_file<P: AsRef<Path>, B: BufRead, D: Demangler>(
+    lines: Lines<B>,
+    filename: P,
+    base_dir: P,
+    excludes: &[&str],
+    options: &ParseOptions,
+    timestamp: u64,
+    demangler: D,
+    xml_options: &XmlOptions,
+) -> anyhow::Result<StreamStats> {
+    let mut buffer = std::io::BufWriter::new(std::fs::File::create(filename)?);
+    buffer.write_all(XML_HEADER.as_bytes())?;
+    let (mut buffer, stats) = stream_xml(
+        lines,
+        buffer,
+        base_dir,
+        excludes,
+        options,
+        timestamp,
+        demangler,
+        xml_options,
+    )?;
+    buffer.flush()?;
+    Ok(stats)
+}
@@ -0,0 +1,256 @@
+//! Per-package and total coverage tables for job logs and merge request comments
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use crate::{CompSummary, CoverageData, Metric, Summary};
+
+/// Output format of the summary table
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SummaryFormat {
+    /// aligned plain text columns, for job logs
+    #[default]
+    Text,
+    /// GitHub/GitLab flavoured Markdown table, for merge request comments
+    Markdown,
+}
+
+impl SummaryFormat {
+    /// file extension used when writing the summary next to the XML file
+    #[must_use]
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Text => "txt",
+            Self::Markdown => "md",
+        }
+    }
+}
+
+impl FromStr for SummaryFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "markdown" | "md" => Ok(Self::Markdown),
+            _ => anyhow::bail!("unknown summary format {s:?}, expected text or markdown"),
+        }
+    }
+}
+
+/// Sort order of the package rows, rates sort worst first
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SummarySort {
+    /// alphabetical by package name
+    #[default]
+    Name,
+    /// by line rate
+    Lines,
+    /// by branch rate
+    Branches,
+    /// by function rate
+    Functions,
+}
+
+impl FromStr for SummarySort {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "name" => Ok(Self::Name),
+            "lines" => Ok(Self::Lines),
+            "branches" => Ok(Self::Branches),
+            "functions" => Ok(Self::Functions),
+            _ => anyhow::bail!(
+                "unknown summary sort {s:?}, expected name, lines, branches or functions"
+            ),
+        }
+    }
+}
+
+impl SummarySort {
+    fn rate(self, summary: &Summary) -> f64 {
+        match self {
+            Self::Name | Self::Lines => summary.rate(Metric::Lines),
+            Self::Branches => summary.rate(Metric::Branches),
+            Self::Functions => summary.rate(Metric::Functions),
+        }
+    }
+
+    fn sort(self, rows: &mut [(String, Summary)]) {
+        match self {
+            Self::Name => rows.sort_by(|a, b| a.0.cmp(&b.0)),
+            _ => rows.sort_by(|a, b| {
+                self.rate(&a.1)
+                    .total_cmp(&self.rate(&b.1))
+                    .then_with(|| a.0.cmp(&b.0))
+            }),
+        }
+    }
+}
+
+/// Options for [`render_summary`]
+#[derive(Debug, Clone, Default)]
+pub struct SummaryOptions {
+    /// output format
+    pub format: SummaryFormat,
+    /// sort order of the package rows
+    pub sort: SummarySort,
+    /// additionally list the N files with the lowest line rate
+    pub worst_files: Option<usize>,
+    /// merge packages nested deeper than this many name components into their parent
+    pub depth: Option<usize>,
+}
+
+fn cell(summary: &Summary, metric: Metric) -> String {
+    let (covered, total) = summary.counts(metric);
+    format!("{:.2}% ({covered}/{total})", summary.rate(metric) * 100.)
+}
+
+fn cells(name: &str, summary: &Summary) -> [String; 4] {
+    [
+        name.to_string(),
+        cell(summary, Metric::Lines),
+        cell(summary, Metric::Branches),
+        cell(summary, Metric::Functions),
+    ]
+}
+
+/// Package name shown in the table, the root package has an empty name
+fn display_name(pkg_name: &str) -> &str {
+    if pkg_name.is_empty() {
+        "(root)"
+    } else {
+        pkg_name
+    }
+}
+
+fn render_table(format: SummaryFormat, rows: &[[String; 4]]) -> String {
+    let mut out = String::new();
+    match format {
+        SummaryFormat::Text => {
+            let mut widths = [0; 4];
+            for row in rows {
+                for (width, cell) in widths.iter_mut().zip(row) {
+                    *width = (*width).max(cell.chars().count());
+                }
+            }
+            for (i, row) in rows.iter().enumerate() {
+                let line = format!(
+                    "{:<w0$}  {:>w1$}  {:>w2$}  {:>w3$}",
+                    row[0],
+                    row[1],
+                    row[2],
+                    row[3],
+                    w0 = widths[0],
+                    w1 = widths[1],
+                    w2 = widths[2],
+                    w3 = widths[3],
+                );
+                out.push_str(line.trim_end());
+                out.push('\n');
+                if i == 0 {
+                    out.push_str(&"-".repeat(widths.iter().sum::<usize>() + 6));
+                    out.push('\n');
+                }
+            }
+        }
+        SummaryFormat::Markdown => {
+            for (i, row) in rows.iter().enumerate() {
+                let escaped: Vec<String> = row.iter().map(|c| c.replace('|', "\\|")).collect();
+                out.push_str("| ");
+                out.push_str(&escaped.join(" | "));
+                out.push_str(" |\n");
+                if i == 0 {
+                    out.push_str("| :--- | ---: | ---: | ---: |\n");
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Renders a per-package and total coverage table, optionally followed by a table of the
+/// files with the lowest line rate.
+#[must_use]
+pub fn render_summary(cov_data: &CoverageData, options: &SummaryOptions) -> String {
+    let mut packages: BTreeMap<String, Summary> = BTreeMap::new();
+    for (pkg_name, package) in &cov_data.packages {
+        let name = match options.depth {
+            Some(depth) => pkg_name
+                .split('.')
+                .take(depth)
+                .collect::<Vec<&str>>()
+                .join("."),
+            None => pkg_name.clone(),
+        };
+        let entry = packages.entry(name).or_default();
+        *entry = std::mem::take(entry) + package.summary();
+    }
+    let mut package_rows: Vec<(String, Summary)> = packages.into_iter().collect();
+    options.sort.sort(&mut package_rows);
+
+    let total = cov_data.summary();
+    let (bold_open, bold_close) = match options.format {
+        SummaryFormat::Text => ("", ""),
+        SummaryFormat::Markdown => ("**", "**"),
+    };
+    let mut rows = vec![[
+        "Package".to_string(),
+        "Lines".to_string(),
+        "Branches".to_string(),
+        "Functions".to_string(),
+    ]];
+    rows.extend(
+        package_rows
+            .iter()
+            .map(|(name, summary)| cells(display_name(name), summary)),
+    );
+    rows.push(cells(&format!("{bold_open}Total{bold_close}"), &total));
+    let mut out = render_table(options.format, &rows);
+
+    if let Some(n) = options.worst_files {
+        let mut files: Vec<(String, Summary)> = cov_data
+            .packages
+            .values()
+            .flat_map(|package| package.classes.iter())
+            .map(|(key, class)| (cov_data.class_path(key), class.summary()))
+            .collect();
+        SummarySort::Lines.sort(&mut files);
+        files.truncate(n);
+        let mut rows = vec![[
+            "File".to_string(),
+            "Lines".to_string(),
+            "Branches".to_string(),
+            "Functions".to_string(),
+        ]];
+        rows.extend(files.iter().map(|(name, summary)| cells(name, summary)));
+        out.push('\n');
+        out.push_str(&render_table(options.format, &rows));
+    }
+    out
+}
+
+/// Single line with the total coverage of all metrics, meant to be matched by GitLab's
+/// `coverage:` regex. The format is stable:
+///
+/// `Total <first> coverage: <rate>% (<covered>/<total>), <second>: <rate>% (<covered>/<total>), <third>: <rate>% (<covered>/<total>)`
+///
+/// with rates printed with two decimals, so `^Total \w+ coverage: (\d+\.\d+)%` always
+/// captures the metric chosen by `first`.
+#[must_use]
+pub fn total_coverage_line(cov_data: &CoverageData, first: Metric) -> String {
+    summary_coverage_line(&cov_data.summary(), first)
+}
+
+/// [`total_coverage_line`] of an already computed total, e.g. from [`crate::StreamStats`]
+#[must_use]
+pub fn summary_coverage_line(total: &Summary, first: Metric) -> String {
+    let mut line = format!("Total {} coverage: {}", first.name(), cell(total, first));
+    for metric in Metric::ALL.into_iter().filter(|m| *m != first) {
+        line.push_str(", ");
+        line.push_str(metric.name());
+        line.push_str(": ");
+        line.push_str(&cell(total, metric));
+    }
+    line
+}
@@ -19,7 +19,7 @@ end_of_record
 
     assert!(result.packages.contains_key("foo"));
     let foo = result.packages.get("foo").unwrap();
-    assert!(foo.classes.contains_key("foo/file.ext"));
+    assert!(foo.classes.contains_key(&(0, "foo/file.ext".to_string())));
     let foo_summary = foo.summary();
     assert_eq!(foo_summary.branches_covered, 1);
     assert_eq!(foo_summary.branches_total, 2);
@@ -27,7 +27,7 @@ end_of_record
     assert_eq!(foo_summary.line_rate(), 0.5);
     assert_eq!(foo_summary.lines_covered, 1);
     assert_eq!(foo_summary.lines_total, 2);
-    let class = foo.classes.get("foo/file.ext").unwrap();
+    let class = foo.classes.get(&(0, "foo/file.ext".to_string())).unwrap();
     let class_summary = class.summary();
     assert_eq!(class_summary.branches_covered, 1);
     assert_eq!(class_summary.branches_total, 2);
@@ -40,7 +40,7 @@ fn test_parse_with_functions() {
     let lcov = "TN:\nSF:foo/file.ext\nDA:1,1\nDA:2,0\nFN:1,(anonymous_1)\nFN:2,namedFn\nFNDA:1,(anonymous_1)\nend_of_record\n";
     let result = parse_lines(lcov.as_bytes().lines(), "", &[]).unwrap();
     let foo = result.packages.get("foo").unwrap();
-    let class = foo.classes.get("foo/file.ext").unwrap();
+    let class = foo.classes.get(&(0, "foo/file.ext".to_string())).unwrap();
     let foo_summary = foo.summary();
     assert_eq!(foo_summary.line_rate(), 0.5);
     assert_eq!(foo_summary.lines_covered, 1);
@@ -59,7 +59,7 @@ fn test_parse_with_checksum() {
     assert_eq!(foo_summary.branches_total, 2);
     assert_eq!(foo_summary.lines_covered, 1);
     assert_eq!(foo_summary.lines_total, 2);
-    let class = foo.classes.get("foo/file.ext").unwrap();
+    let class = foo.classes.get(&(0, "foo/file.ext".to_string())).unwrap();
     let class_summary = class.summary();
     assert_eq!(class_summary.branches_covered, 1);
     assert_eq!(class_summary.branches_total, 2);
@@ -85,7 +85,7 @@ fn test_method_name_with_comma() {
     let lcov =  "TN:\nSF:foo/file.ext\nDA:1,1\nDA:2,0\nFN:1,(anonymous_1<foo, bar>)\nFN:2,namedFn\nFNDA:1,(anonymous_1<foo, bar>)\nend_of_record\n";
     let result = parse_lines(lcov.as_bytes().lines(), "", &[]).unwrap();
     let foo = result.packages.get("foo").unwrap();
-    let class = foo.classes.get("foo/file.ext").unwrap();
+    let class = foo.classes.get(&(0, "foo/file.ext".to_string())).unwrap();
     assert!(class.methods.contains_key("namedFn"));
     assert!(class.methods.contains_key("(anonymous_1<foo, bar>)"));
 }
@@ -232,3 +232,1652 @@ fn test_demangle_rust() {
 </coverage>"#;
     assert_eq!(lcov_xml, xml);
 }
+
+#[cfg(feature = "html")]
+#[test]
+fn test_html_report() {
+    let lcov = "TN:\nSF:foo/file.ext\nDA:1,1\nDA:2,0\nBRDA:1,1,1,1\nBRDA:1,1,2,0\nFN:1,namedFn\nFNDA:1,namedFn\nend_of_record\n";
+    let result = parse_lines(lcov.as_bytes().lines(), ".", &[]).unwrap();
+    let out_dir = std::env::temp_dir().join("lcov2cobertura_test_html_report");
+    coverage_to_html(&out_dir, &result, demangle::NullDemangler::new()).unwrap();
+    let index = std::fs::read_to_string(out_dir.join("index.html")).unwrap();
+    assert!(index.contains("<a href=\"files/foo_file_ext.html\">foo/file.ext</a>"));
+    assert!(index.contains("50.00% (1/2)"));
+    assert!(index.contains("100.00% (1/1)"));
+    let page = std::fs::read_to_string(out_dir.join("files/foo_file_ext.html")).unwrap();
+    assert!(page.contains("Source file not available."));
+    assert!(page.contains("<tr class=\"partial\"><td class=\"num\">1</td><td class=\"num\">1</td><td class=\"num\">1/2</td>"));
+    assert!(page.contains("<tr class=\"miss\"><td class=\"num\">2</td><td class=\"num\">0</td>"));
+    std::fs::remove_dir_all(out_dir).unwrap();
+}
+
+#[test]
+fn test_render_summary() {
+    let lcov = "SF:src/a/x.rs\nDA:1,1\nDA:2,0\nFN:1,f\nFNDA:1,f\nend_of_record\nSF:src/b/y.rs\nDA:1,1\nDA:2,1\nBRDA:1,1,1,1\nBRDA:1,1,2,0\nend_of_record\nSF:src/b/c/z.rs\nDA:1,0\nend_of_record\n";
+    let result = parse_lines(lcov.as_bytes().lines(), "", &[]).unwrap();
+    let options = SummaryOptions {
+        sort: SummarySort::Lines,
+        ..SummaryOptions::default()
+    };
+    let text = render_summary(&result, &options);
+    let expected = "\
+Package          Lines      Branches      Functions
+---------------------------------------------------
+src.b.c    0.00% (0/1)   0.00% (0/0)    0.00% (0/0)
+src.a     50.00% (1/2)   0.00% (0/0)  100.00% (1/1)
+src.b    100.00% (2/2)  50.00% (1/2)    0.00% (0/0)
+Total     60.00% (3/5)  50.00% (1/2)  100.00% (1/1)
+";
+    assert_eq!(text, expected);
+
+    let options = SummaryOptions {
+        format: SummaryFormat::Markdown,
+        worst_files: Some(1),
+        depth: Some(2),
+        ..SummaryOptions::default()
+    };
+    let markdown = render_summary(&result, &options);
+    let expected = "\
+| Package | Lines | Branches | Functions |
+| :--- | ---: | ---: | ---: |
+| src.a | 50.00% (1/2) | 0.00% (0/0) | 100.00% (1/1) |
+| src.b | 66.67% (2/3) | 50.00% (1/2) | 0.00% (0/0) |
+| **Total** | 60.00% (3/5) | 50.00% (1/2) | 100.00% (1/1) |
+
+| File | Lines | Branches | Functions |
+| :--- | ---: | ---: | ---: |
+| src/b/c/z.rs | 0.00% (0/1) | 0.00% (0/0) | 0.00% (0/0) |
+";
+    assert_eq!(markdown, expected);
+}
+
+#[test]
+fn test_total_coverage_line() {
+    let lcov = "SF:foo/file.ext\nDA:1,1\nDA:2,0\nDA:3,1\nBRDA:1,1,1,1\nBRDA:1,1,2,0\nFN:1,f\nFNDA:1,f\nend_of_record\n";
+    let result = parse_lines(lcov.as_bytes().lines(), "", &[]).unwrap();
+    assert_eq!(
+        total_coverage_line(&result, Metric::Lines),
+        "Total line coverage: 66.67% (2/3), branch: 50.00% (1/2), function: 100.00% (1/1)"
+    );
+    assert_eq!(
+        total_coverage_line(&result, Metric::Branches),
+        "Total branch coverage: 50.00% (1/2), line: 66.67% (2/3), function: 100.00% (1/1)"
+    );
+}
+
+#[test]
+fn test_check_thresholds() {
+    let lcov = "SF:src/a/x.rs\nDA:1,1\nDA:2,0\nFN:1,f\nFNDA:1,f\nend_of_record\nSF:src/b/y.rs\nDA:1,1\nDA:2,1\nDA:3,1\nend_of_record\n";
+    let result = parse_lines(lcov.as_bytes().lines(), "", &[]).unwrap();
+    let total = Thresholds {
+        lines: Some(90.),
+        branches: Some(50.), // no branches at all, never fails
+        functions: Some(100.),
+    };
+    let rules = [
+        ThresholdRule {
+            scope: ThresholdScope::Package,
+            pattern: "^src\\.".to_string(),
+            thresholds: Thresholds {
+                lines: Some(60.),
+                ..Thresholds::default()
+            },
+        },
+        ThresholdRule {
+            scope: ThresholdScope::File,
+            pattern: "y\\.rs$".to_string(),
+            thresholds: Thresholds {
+                functions: Some(10.),
+                ..Thresholds::default()
+            },
+        },
+    ];
+    let failures = check_thresholds(&result, &total, &rules).unwrap();
+    let failures: Vec<String> = failures.iter().map(ToString::to_string).collect();
+    assert_eq!(
+        failures,
+        [
+            "total: line coverage 80.00% is below 90.00%",
+            "package src.a: line coverage 50.00% is below 60.00%",
+        ]
+    );
+    assert!(check_thresholds(&result, &Thresholds::default(), &[])
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn test_diff_coverage() {
+    let diff = "\
+diff --git a/src/a/x.rs b/src/a/x.rs
+index 1111111..2222222 100644
+--- a/src/a/x.rs
++++ b/src/a/x.rs
+@@ -1,0 +2,3 @@ fn main() {
++    let a = 1;
++--- not a header
++    let c = 3;
+@@ -9 +11,0 @@
+-    removed();
+diff --git a/README.md b/README.md
+--- a/README.md
++++ b/README.md
+@@ -1,3 +1,3 @@
+ context
+-old
++new
+ context
+diff --git a/gone.rs b/gone.rs
+--- a/gone.rs
++++ /dev/null
+@@ -1 +0,0 @@
+-fn gone() {}
+";
+    let changed = parse_unified_diff(diff).unwrap();
+    assert_eq!(changed.len(), 2);
+    assert_eq!(
+        changed
+            .get("src/a/x.rs")
+            .unwrap()
+            .iter()
+            .copied()
+            .collect::<Vec<usize>>(),
+        [2, 3, 4]
+    );
+    assert_eq!(
+        changed
+            .get("README.md")
+            .unwrap()
+            .iter()
+            .copied()
+            .collect::<Vec<usize>>(),
+        [2]
+    );
+
+    let lcov = "SF:/builds/proj/src/a/x.rs\nDA:1,1\nDA:2,1\nDA:3,0\nDA:4,0\nDA:5,0\nend_of_record\nSF:/builds/proj/src/b/y.rs\nDA:2,0\nend_of_record\n";
+    let result = parse_lines(lcov.as_bytes().lines(), "/builds/proj", &[]).unwrap();
+    let coverage = diff_coverage(&result, &changed);
+    assert_eq!(
+        coverage.files,
+        [FileDiffCoverage {
+            file_name: "src/a/x.rs".to_string(),
+            covered: 1,
+            total: 3,
+            uncovered_lines: vec![3, 4],
+        }]
+    );
+    assert_eq!(
+        coverage.render(),
+        "src/a/x.rs: 33.33% (1/3), uncovered: 3-4\nDiff coverage: 33.33% (1/3)\n"
+    );
+}
+
+#[test]
+fn test_diff_coverage_matching() {
+    // files of several source roots match by their path including the root
+    let lcov = "SF:/work/a/src/lib.rs\nDA:1,1\nend_of_record\nSF:/work/b/src/lib.rs\nDA:1,0\nend_of_record\n";
+    let options = ParseOptions {
+        source_roots: vec!["/work/a".to_string(), "/work/b".to_string()],
+        ..ParseOptions::default()
+    };
+    let result = parse_lines_with(lcov.as_bytes().lines(), "/work", &[], &options).unwrap();
+    let changed = parse_unified_diff(
+        "+++ b/a/src/lib.rs\n@@ -0,0 +1 @@\n+x\n+++ b/b/src/lib.rs\n@@ -0,0 +1 @@\n+x\n",
+    )
+    .unwrap();
+    let coverage = diff_coverage(&result, &changed);
+    let files: Vec<(&str, usize)> = coverage
+        .files
+        .iter()
+        .map(|f| (f.file_name.as_str(), f.covered))
+        .collect();
+    assert_eq!(files, [("a/src/lib.rs", 1), ("b/src/lib.rs", 0)]);
+    assert!(coverage.ambiguous.is_empty());
+
+    // paths matching several files of the other side are reported instead of picking one
+    let lcov = "SF:/proj/a/lib.rs\nDA:1,1\nend_of_record\nSF:/proj/b/lib.rs\nDA:1,1\nend_of_record\nSF:/proj/c/main.rs\nDA:1,1\nend_of_record\n";
+    let result = parse_lines(lcov.as_bytes().lines(), "/proj", &[]).unwrap();
+    let changed = parse_unified_diff(
+        "+++ b/lib.rs\n@@ -0,0 +1 @@\n+x\n+++ b/x/c/main.rs\n@@ -0,0 +1 @@\n+x\n+++ b/y/c/main.rs\n@@ -0,0 +1 @@\n+x\n",
+    )
+    .unwrap();
+    let coverage = diff_coverage(&result, &changed);
+    assert!(coverage.files.is_empty());
+    assert_eq!(
+        coverage.ambiguous,
+        [
+            AmbiguousMatch {
+                path: "c/main.rs".to_string(),
+                candidates: vec!["x/c/main.rs".to_string(), "y/c/main.rs".to_string()],
+            },
+            AmbiguousMatch {
+                path: "lib.rs".to_string(),
+                candidates: vec!["a/lib.rs".to_string(), "b/lib.rs".to_string()],
+            },
+        ]
+    );
+    assert_eq!(
+        coverage.render(),
+        "c/main.rs is ambiguous, matches x/c/main.rs, y/c/main.rs, not counted\n\
+         lib.rs is ambiguous, matches a/lib.rs, b/lib.rs, not counted\n\
+         Diff coverage: no instrumented lines changed\n"
+    );
+    // an equal path wins over suffix matches
+    let changed = parse_unified_diff("+++ b/a/lib.rs\n@@ -0,0 +1 @@\n+x\n").unwrap();
+    let coverage = diff_coverage(&result, &changed);
+    let files: Vec<&str> = coverage
+        .files
+        .iter()
+        .map(|f| f.file_name.as_str())
+        .collect();
+    assert_eq!(files, ["a/lib.rs"]);
+    assert!(coverage.ambiguous.is_empty());
+}
+
+#[test]
+fn test_parse_cobertura_roundtrip() {
+    let lcov = "TN:\nSF:foo/file.ext\nDA:1,1\nDA:2,0\nBRDA:1,1,1,1\nBRDA:1,1,2,0\nFN:1,(anonymous_1)\nFN:2,namedFn\nFNDA:1,(anonymous_1)\nend_of_record\n";
+    let result = parse_lines(lcov.as_bytes().lines(), ".", &[]).unwrap();
+    let xml = coverage_to_string(&result, 1_346_815_648_000, NullDemangler::new()).unwrap();
+    let parsed = parse_cobertura(xml.as_bytes(), &[]).unwrap();
+    assert_eq!(parsed.base_dir, ".");
+    let class = parsed
+        .packages
+        .get("foo")
+        .unwrap()
+        .classes
+        .get(&(0, "foo/file.ext".to_string()))
+        .unwrap();
+    assert_eq!(class.name, "foo.file.ext");
+    assert_eq!(class.methods.get("(anonymous_1)"), Some(&(1, 1)));
+    assert_eq!(class.methods.get("namedFn"), Some(&(2, 0)));
+    let xml_again = coverage_to_string(&parsed, 1_346_815_648_000, NullDemangler::new()).unwrap();
+    assert_eq!(xml, xml_again);
+}
+
+#[test]
+fn test_parse_cobertura_branch_without_condition_coverage() {
+    let xml = r#"<coverage><packages><package name="src"><classes>
+        <class name="src.a.rs" filename="src/a.rs"><lines>
+            <line number="1" hits="1" branch="true"/>
+            <line number="2" hits="0" branch="true" condition-coverage="50% (1/2)"/>
+        </lines></class>
+    </classes></package></packages></coverage>"#;
+    let parsed = parse_cobertura(xml.as_bytes(), &[]).unwrap();
+    let class = parsed
+        .packages
+        .get("src")
+        .unwrap()
+        .classes
+        .get(&(0, "src/a.rs".to_string()))
+        .unwrap();
+    assert!(!class.lines.get(&1).unwrap().branch);
+    assert!(class.lines.get(&2).unwrap().branch);
+    let xml = coverage_to_string(&parsed, 0, NullDemangler::new()).unwrap();
+    assert!(
+        xml.contains(r#"<line branch="false" hits="1" number="1"/>"#),
+        "{xml}"
+    );
+    assert!(xml.contains(r#"condition-coverage="50% (1/2)""#), "{xml}");
+}
+
+#[test]
+fn test_compare() {
+    let before = "SF:src/a/x.rs\nDA:1,1\nDA:2,1\nDA:3,0\nFN:1,f\nFNDA:1,f\nend_of_record\nSF:src/b/old.rs\nDA:1,1\nend_of_record\n";
+    let after = "SF:src/a/x.rs\nDA:1,1\nDA:2,0\nDA:3,0\nFN:1,f\nFNDA:0,f\nend_of_record\nSF:src/b/new.rs\nDA:1,1\nend_of_record\n";
+    let before = parse_lines(before.as_bytes().lines(), "", &[]).unwrap();
+    let after = parse_lines(after.as_bytes().lines(), "", &[]).unwrap();
+    let comparison = compare(&before, &after);
+    assert_eq!(
+        comparison.added_files().collect::<Vec<_>>(),
+        ["src/b/new.rs"]
+    );
+    assert_eq!(
+        comparison.removed_files().collect::<Vec<_>>(),
+        ["src/b/old.rs"]
+    );
+    assert_eq!(
+        comparison.newly_uncovered_lines,
+        [("src/a/x.rs".to_string(), vec![2])]
+    );
+    assert_eq!(
+        comparison.functions_lost,
+        [("src/a/x.rs".to_string(), "f".to_string())]
+    );
+    assert!((comparison.regression() - 25.).abs() < 1e-9);
+    let text = "\
+Total: lines 75.00% -> 50.00% (-25.00), branches 0.00% -> 0.00% (+0.00)
+
+Changed packages:
+  src.a: lines 66.67% -> 33.33% (-33.33), branches 0.00% -> 0.00% (+0.00)
+
+Changed files:
+  src/a/x.rs: lines 66.67% -> 33.33% (-33.33), branches 0.00% -> 0.00% (+0.00)
+
+Added files:
+  src/b/new.rs
+
+Removed files:
+  src/b/old.rs
+
+Newly uncovered lines:
+  src/a/x.rs: 2
+
+Functions that lost coverage:
+  src/a/x.rs: f
+";
+    assert_eq!(comparison.render(CompareFormat::Text), text);
+    let json = comparison.render(CompareFormat::Json);
+    assert!(json.starts_with("{\"total\": {\"name\": \"total\", \"before\": {\"lines_covered\": 3, \"lines_total\": 4, \"line_rate\": 0.7500,"));
+    assert!(json.contains("\"added_files\": [\"src/b/new.rs\"], \"removed_files\": [\"src/b/old.rs\"], \"newly_uncovered_lines\": [{\"file\": \"src/a/x.rs\", \"lines\": [2]}], \"functions_lost\": [{\"file\": \"src/a/x.rs\", \"function\": \"f\"}]}"));
+}
+
+#[test]
+fn test_parse_filters() {
+    let lcov = "SF:src/lib.rs\nDA:1,1\nend_of_record\nSF:src/generated/schema.rs\nDA:1,0\nend_of_record\nSF:src/db/schema.rs\nDA:1,0\nend_of_record\nSF:tests/it.rs\nDA:1,1\nend_of_record\nSF:src/main.rs\nDA:1,1\nBRDA:1,1,1,1\nend_of_record\n";
+    let classes = |options: &ParseOptions| {
+        let result = parse_lines_with(lcov.as_bytes().lines(), "", &[], options).unwrap();
+        let mut files: Vec<String> = result
+            .packages
+            .values()
+            .flat_map(|p| p.classes.keys().map(|(_, f)| f.clone()))
+            .collect();
+        files.sort();
+        (files, result.filtered_records())
+    };
+    assert_eq!(
+        classes(&ParseOptions::default()),
+        (
+            vec![
+                "src/db/schema.rs".to_string(),
+                "src/generated/schema.rs".to_string(),
+                "src/lib.rs".to_string(),
+                "src/main.rs".to_string(),
+                "tests/it.rs".to_string(),
+            ],
+            0
+        )
+    );
+    let options = ParseOptions {
+        include: vec!["src/**".to_string()],
+        exclude_file: vec!["src/generated/".to_string(), "main.rs".to_string()],
+        ..ParseOptions::default()
+    };
+    assert_eq!(
+        classes(&options),
+        (
+            vec!["src/db/schema.rs".to_string(), "src/lib.rs".to_string()],
+            3
+        )
+    );
+    let options = ParseOptions {
+        exclude_file: vec!["schema.rs".to_string(), "/tests/*.rs".to_string()],
+        ..ParseOptions::default()
+    };
+    assert_eq!(
+        classes(&options),
+        (vec!["src/lib.rs".to_string(), "src/main.rs".to_string()], 3)
+    );
+    assert!(parse_lines_with(
+        lcov.as_bytes().lines(),
+        "",
+        &[],
+        &ParseOptions {
+            include: vec!["src/[z-a].rs".to_string()],
+            ..ParseOptions::default()
+        }
+    )
+    .is_err());
+}
+
+#[test]
+fn test_path_map() {
+    let lcov = "SF:/builds/group/proj/src/a/x.rs\nDA:1,1\nend_of_record\nSF:/usr/src/app/src/b/y.rs\nDA:1,0\nend_of_record\nSF:/usr/include/stdio.h\nDA:1,1\nend_of_record\nSF:/builds/group/project2/z.rs\nDA:1,1\nend_of_record\n";
+    let path_maps: Vec<PathMap> = [
+        "/builds/group/proj=/checkout",
+        r"regex:^/usr/src/\w+/=/checkout/",
+    ]
+    .iter()
+    .map(|m| m.parse().unwrap())
+    .collect();
+    let mut options = ParseOptions {
+        path_maps,
+        ..ParseOptions::default()
+    };
+    let files = |options: &ParseOptions| {
+        let result = parse_lines_with(lcov.as_bytes().lines(), "/checkout", &[], options).unwrap();
+        let mut files: Vec<(String, String)> = result
+            .packages
+            .iter()
+            .flat_map(|(name, p)| p.classes.keys().map(|(_, f)| (name.clone(), f.clone())))
+            .collect();
+        files.sort();
+        (files, result.filtered_records())
+    };
+    let (all, filtered) = files(&options);
+    assert_eq!(filtered, 0);
+    assert_eq!(
+        all[2..],
+        [
+            ("src.a".to_string(), "src/a/x.rs".to_string()),
+            ("src.b".to_string(), "src/b/y.rs".to_string()),
+        ]
+    );
+    assert_eq!(all.len(), 4);
+    options.drop_outside_base_dir = true;
+    assert_eq!(files(&options), (all[2..].to_vec(), 2));
+
+    assert!("no-separator".parse::<PathMap>().is_err());
+    assert!("=/x".parse::<PathMap>().is_err());
+    assert!("regex:(=x".parse::<PathMap>().is_err());
+    let relative: PathMap = "/build/=".parse().unwrap();
+    assert_eq!(
+        relative.apply("/build/src/a.rs").as_deref(),
+        Some("src/a.rs")
+    );
+    assert_eq!(relative.apply("/buildx/a.rs"), None);
+}
+
+#[test]
+fn test_path_normalization() {
+    let lcov = "SF:/checkout/sub/./foo//bar/../file.ext\nDA:1,1\nend_of_record\nSF:/checkout/sub/foo/file.ext\nDA:2,0\nend_of_record\nSF:/checkout/other/x.rs\nDA:1,1\nend_of_record\nSF:/x/../../checkout/sub/y.rs\nDA:1,1\nend_of_record\n";
+    let result = parse_lines(lcov.as_bytes().lines(), "/checkout/sub/", &[]).unwrap();
+    let class = result
+        .packages
+        .get("foo")
+        .unwrap()
+        .classes
+        .get(&(0, "foo/file.ext".to_string()))
+        .unwrap();
+    assert_eq!(class.lines.len(), 2);
+    assert_eq!(class.name, "foo.file.ext");
+    assert!(result
+        .packages
+        .get("...other")
+        .unwrap()
+        .classes
+        .contains_key(&(0, "../other/x.rs".to_string())));
+    assert!(result
+        .packages
+        .get("")
+        .unwrap()
+        .classes
+        .contains_key(&(0, "y.rs".to_string())));
+    assert_eq!(result.packages.len(), 3);
+
+    let options = ParseOptions {
+        drop_outside_base_dir: true,
+        ..ParseOptions::default()
+    };
+    let result = parse_lines_with(lcov.as_bytes().lines(), "/checkout/sub", &[], &options).unwrap();
+    assert_eq!(result.filtered_records(), 1);
+
+    // without a common directory absolute paths stay absolute, whatever the current directory
+    let lcov = "SF:/usr/include/stdio.h\nDA:1,1\nend_of_record\n";
+    for base_dir in ["/checkout/sub", ".", "src"] {
+        let result = parse_lines(lcov.as_bytes().lines(), base_dir, &[]).unwrap();
+        let files: Vec<&ClassKey> = result
+            .packages
+            .values()
+            .flat_map(|p| p.classes.keys())
+            .collect();
+        assert_eq!(
+            files,
+            [&(0, "/usr/include/stdio.h".to_string())],
+            "{base_dir}"
+        );
+    }
+    let result = parse_lines_with(lcov.as_bytes().lines(), "/checkout/sub", &[], &options).unwrap();
+    assert_eq!(result.filtered_records(), 1);
+
+    // canonicalization falls back to the lexical path for files that do not exist
+    let options = ParseOptions {
+        canonicalize_paths: true,
+        ..ParseOptions::default()
+    };
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let lcov = format!(
+        "SF:{manifest_dir}/src/../src/lib.rs\nDA:1,1\nend_of_record\nSF:{manifest_dir}/missing/../src/gone.rs\nDA:1,1\nend_of_record\n"
+    );
+    let result = parse_lines_with(lcov.as_bytes().lines(), manifest_dir, &[], &options).unwrap();
+    let classes = &result.packages.get("src").unwrap().classes;
+    assert!(classes.contains_key(&(0, "src/lib.rs".to_string())));
+    assert!(classes.contains_key(&(0, "src/gone.rs".to_string())));
+}
+
+#[test]
+fn test_duplicate_records() {
+    let lcov = "SF:src/a.rs\nFN:1,f\nFNDA:5,f\nDA:1,5\nend_of_record\nSF:./src/a.rs\nFN:1,f\nFNDA:0,f\nDA:1,0\nend_of_record\n";
+    let result = parse_lines(lcov.as_bytes().lines(), "", &[]).unwrap();
+    let class = result
+        .packages
+        .get("src")
+        .unwrap()
+        .classes
+        .get(&(0, "src/a.rs".to_string()))
+        .unwrap();
+    assert_eq!(class.lines.get(&1).unwrap().hits, 5);
+    assert_eq!(class.methods.get("f"), Some(&(1, 5)));
+    let xml = coverage_to_string(&result, 0, NullDemangler::new()).unwrap();
+    assert!(xml.contains(r#"<line hits="5" number="1" branch="false"/>"#));
+}
+
+#[test]
+fn test_windows_paths() {
+    let lcov = "SF:C:\\src\\proj\\foo\\foo.cpp\nDA:1,1\nend_of_record\nSF:c:/src/proj/foo/bar.cpp\nDA:1,0\nend_of_record\nSF:D:\\other\\x.cpp\nDA:1,1\nend_of_record\n";
+    let result = parse_lines(lcov.as_bytes().lines(), "C:\\src\\proj", &[]).unwrap();
+    let foo = &result.packages.get("foo").unwrap().classes;
+    let sep = std::path::MAIN_SEPARATOR;
+    let class = foo.get(&(0, format!("foo{sep}foo.cpp"))).unwrap();
+    assert_eq!(class.name, "foo.foo.cpp");
+    assert!(foo.contains_key(&(0, format!("foo{sep}bar.cpp"))));
+
+    let options = ParseOptions {
+        drop_outside_base_dir: true,
+        path_style: PathStyle::Windows,
+        ..ParseOptions::default()
+    };
+    let result = parse_lines_with(lcov.as_bytes().lines(), "C:/src/proj/", &[], &options).unwrap();
+    assert_eq!(result.filtered_records(), 1);
+    let foo = &result.packages.get("foo").unwrap().classes;
+    assert!(foo.contains_key(&(0, "foo\\foo.cpp".to_string())));
+    assert!(foo.contains_key(&(0, "foo\\bar.cpp".to_string())));
+
+    let options = ParseOptions {
+        path_style: PathStyle::Unix,
+        ..ParseOptions::default()
+    };
+    let lcov = "SF:src\\a\\x.rs\nDA:1,1\nend_of_record\n";
+    let result = parse_lines_with(lcov.as_bytes().lines(), "", &[], &options).unwrap();
+    assert!(result
+        .packages
+        .get("src.a")
+        .unwrap()
+        .classes
+        .contains_key(&(0, "src/a/x.rs".to_string())));
+}
+
+#[test]
+fn test_package_naming() {
+    let lcov = "SF:src/core/a/x.rs\nDA:1,1\nend_of_record\nSF:src/core/b/y.rs\nDA:1,0\nend_of_record\nSF:src/plugins/http/z.rs\nDA:1,1\nend_of_record\nSF:main.rs\nDA:1,1\nend_of_record\n";
+    let packages = |package_naming: PackageNaming| {
+        let options = ParseOptions {
+            package_naming,
+            ..ParseOptions::default()
+        };
+        let result = parse_lines_with(lcov.as_bytes().lines(), "", &[], &options).unwrap();
+        let mut packages: Vec<(String, usize)> = result
+            .packages
+            .iter()
+            .map(|(name, p)| (name.clone(), p.summary().lines_total))
+            .collect();
+        packages.sort();
+        packages
+    };
+    let owned = |packages: &[(&str, usize)]| {
+        packages
+            .iter()
+            .map(|(name, lines)| ((*name).to_string(), *lines))
+            .collect::<Vec<(String, usize)>>()
+    };
+    assert_eq!(
+        packages(PackageNaming::default()),
+        owned(&[
+            ("", 1),
+            ("src.core.a", 1),
+            ("src.core.b", 1),
+            ("src.plugins.http", 1)
+        ])
+    );
+    assert_eq!(
+        packages("depth:2".parse().unwrap()),
+        owned(&[("", 1), ("src.core", 2), ("src.plugins", 1)])
+    );
+    assert_eq!(
+        packages(r"regex:^src/(\w+)/".parse().unwrap()),
+        owned(&[("", 1), ("core", 2), ("plugins", 1)])
+    );
+    assert_eq!(
+        packages(PackageNaming::Mapping(vec![
+            ("src".to_string(), "other".to_string()),
+            ("src/core/".to_string(), "core".to_string()),
+        ])),
+        owned(&[("", 1), ("core", 2), ("other", 1)])
+    );
+    assert!("depth:x".parse::<PackageNaming>().is_err());
+    assert!("flat".parse::<PackageNaming>().is_err());
+}
+
+#[test]
+fn test_source_roots() {
+    let lcov = "SF:/work/app/src/main.rs\nDA:1,1\nend_of_record\nSF:/work/app/vendor/lib/src/lib.rs\nDA:1,0\nend_of_record\nSF:/work/other/x.rs\nDA:1,1\nend_of_record\n";
+    let options = ParseOptions {
+        source_roots: vec!["/work/app/vendor/lib".to_string()],
+        ..ParseOptions::default()
+    };
+    let result = parse_lines_with(lcov.as_bytes().lines(), "/work/app", &[], &options).unwrap();
+    assert_eq!(
+        result.sources().collect::<Vec<&str>>(),
+        ["/work/app", "/work/app/vendor/lib"]
+    );
+    let classes = &result.packages.get("src").unwrap().classes;
+    assert_eq!(
+        result.source_of(classes.get(&(0, "src/main.rs".to_string())).unwrap()),
+        "/work/app"
+    );
+    assert_eq!(
+        result.source_of(classes.get(&(1, "src/lib.rs".to_string())).unwrap()),
+        "/work/app/vendor/lib"
+    );
+    assert_eq!(result.class_source("../other/x.rs"), Some("/work/app"));
+    assert_eq!(result.class_source("missing.rs"), None);
+
+    let xml = coverage_to_string(&result, 1_346_815_648_000, NullDemangler::new()).unwrap();
+    assert!(xml.contains(
+        "<sources>\n        <source>/work/app</source>\n        <source>/work/app/vendor/lib</source>\n    </sources>"
+    ));
+    let parsed = parse_cobertura(xml.as_bytes(), &[]).unwrap();
+    assert_eq!(
+        parsed.sources().collect::<Vec<&str>>(),
+        ["/work/app", "/work/app/vendor/lib"]
+    );
+}
+
+#[test]
+fn test_source_roots_same_relative_name() {
+    let lcov = "SF:/repoA/src/lib.rs\nDA:1,1\nDA:2,0\nend_of_record\nSF:/repoB/src/lib.rs\nDA:5,1\nend_of_record\n";
+    let options = ParseOptions {
+        source_roots: vec!["/repoA".to_string(), "/repoB".to_string()],
+        ..ParseOptions::default()
+    };
+    let result = parse_lines_with(lcov.as_bytes().lines(), "/work", &[], &options).unwrap();
+    let classes = &result.packages.get("src").unwrap().classes;
+    assert_eq!(classes.len(), 2);
+    let a = classes.get(&(1, "src/lib.rs".to_string())).unwrap();
+    let b = classes.get(&(2, "src/lib.rs".to_string())).unwrap();
+    let mut a_lines: Vec<usize> = a.lines.keys().copied().collect();
+    a_lines.sort_unstable();
+    assert_eq!(a_lines, [1, 2]);
+    assert_eq!(b.lines.keys().copied().collect::<Vec<_>>(), [5]);
+
+    let xml = coverage_to_string(&result, 0, NullDemangler::new()).unwrap();
+    assert_eq!(xml.matches(r#"filename="src/lib.rs""#).count(), 2);
+    assert!(xml.contains(r#"name="src.lib.rs" source="/repoA">"#));
+    assert!(xml.contains(r#"name="src.lib.rs" source="/repoB">"#));
+    let parsed = parse_cobertura(xml.as_bytes(), &[]).unwrap();
+    assert_eq!(parsed.packages.get("src").unwrap().classes.len(), 2);
+    let comparison = compare(&result, &parsed);
+    let files: Vec<&str> = comparison.files.iter().map(|f| f.name.as_str()).collect();
+    assert_eq!(files, ["/repoA/src/lib.rs", "/repoB/src/lib.rs"]);
+
+    // a single root writes no `source` attribute
+    let xml = coverage_to_string(
+        &parse_lines(lcov.as_bytes().lines(), "/repoA", &[]).unwrap(),
+        0,
+        NullDemangler::new(),
+    )
+    .unwrap();
+    assert!(!xml.contains(" source="));
+}
+
+#[test]
+fn test_merge() {
+    let a = "SF:src/a/x.rs\nDA:1,1\nDA:2,0\nBRDA:1,0,0,1\nBRDA:1,0,1,0\nFN:1,f\nFNDA:0,f\nend_of_record\n";
+    let b = "SF:src/a/x.rs\nDA:1,2\nDA:2,3\nBRDA:1,0,0,0\nBRDA:1,0,1,0\nFN:1,f\nFNDA:4,f\nend_of_record\nSF:src/b/y.rs\nDA:1,0\nend_of_record\n";
+    let mut merged = parse_lines(a.as_bytes().lines(), "", &[]).unwrap();
+    merged.merge(parse_lines(b.as_bytes().lines(), "", &[]).unwrap());
+    let class = merged
+        .packages
+        .get("src.a")
+        .unwrap()
+        .classes
+        .get(&(0, "src/a/x.rs".to_string()))
+        .unwrap();
+    assert_eq!(class.lines.get(&1).unwrap().hits, 3);
+    assert_eq!(class.lines.get(&2).unwrap().hits, 3);
+    assert_eq!(class.methods.get("f"), Some(&(1, 4)));
+    let summary = merged.summary();
+    assert_eq!(
+        (summary.lines_covered, summary.lines_total),
+        (2, 3),
+        "{summary:?}"
+    );
+    assert_eq!((summary.branches_covered, summary.branches_total), (1, 2));
+
+    // complementary branches of two runs unite like `lcov` merges them
+    let a = "SF:src/a/x.rs\nBRDA:1,0,0,1\nBRDA:1,0,1,0\nend_of_record\n";
+    let b = "SF:src/a/x.rs\nBRDA:1,0,0,0\nBRDA:1,0,1,2\nend_of_record\n";
+    let mut merged = parse_lines(a.as_bytes().lines(), "", &[]).unwrap();
+    merged.merge(parse_lines(b.as_bytes().lines(), "", &[]).unwrap());
+    let summary = merged.summary();
+    assert_eq!((summary.branches_covered, summary.branches_total), (2, 2));
+    let sequential = parse_lines(format!("{a}{b}").as_bytes().lines(), "", &[]).unwrap();
+    let summary = sequential.summary();
+    assert_eq!((summary.branches_covered, summary.branches_total), (2, 2));
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn test_parse_files_parallel() {
+    let dir = std::env::temp_dir().join(format!("lcov2cobertura-parallel-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let files: Vec<std::path::PathBuf> = (0..8)
+        .map(|i| {
+            let path = dir.join(format!("{i}.info"));
+            let lcov = format!("SF:src/a/x.rs\nDA:1,1\nDA:{},0\nend_of_record\nSF:src/f{i}.rs\nDA:1,{i}\nend_of_record\n", i + 2);
+            std::fs::write(&path, lcov).unwrap();
+            path
+        })
+        .collect();
+    let result = parse_files_parallel(
+        &files,
+        std::path::Path::new(""),
+        &[],
+        &ParseOptions::default(),
+        Some(3),
+    )
+    .unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    let class = result
+        .packages
+        .get("src.a")
+        .unwrap()
+        .classes
+        .get(&(0, "src/a/x.rs".to_string()))
+        .unwrap();
+    assert_eq!(class.lines.get(&1).unwrap().hits, 8);
+    assert_eq!(class.lines.len(), 9);
+    assert_eq!(result.packages.get("src").unwrap().classes.len(), 8);
+    assert_eq!(result.summary().lines_covered, 8);
+}
+
+#[cfg(feature = "cpp-demangle")]
+#[test]
+fn test_itanium_demangler_corpus() {
+    let mut demangler = ItaniumDemangler::new();
+    let corpus = include_str!("testdata/itanium_symbols.tsv");
+    for line in corpus.lines().filter(|l| !l.starts_with('#')) {
+        let (mangled, expected) = line.split_once('\t').unwrap();
+        assert_eq!(demangler.demangle(mangled).unwrap(), expected, "{mangled}");
+    }
+    for ident in ["main", "(anonymous_1)", "_Zinvalid", ""] {
+        assert_eq!(demangler.demangle(ident).unwrap(), ident);
+    }
+
+    let clamp = "_ZN3geo5clampIfEET_S1_S1_S1_";
+    let mut demangler = ItaniumDemangler::new().no_params();
+    assert_eq!(
+        demangler.demangle(clamp).unwrap(),
+        "float geo::clamp<float>"
+    );
+    assert_eq!(
+        demangler.demangle("_ZNK3geo5PointplERKS0_").unwrap(),
+        "geo::Point::operator+"
+    );
+    let mut demangler = ItaniumDemangler::new().no_return_type();
+    assert_eq!(
+        demangler.demangle(clamp).unwrap(),
+        "geo::clamp<float>(float, float, float)"
+    );
+    let mut demangler = ItaniumDemangler::new().no_params().no_return_type();
+    assert_eq!(demangler.demangle(clamp).unwrap(), "geo::clamp<float>");
+}
+
+#[test]
+fn test_stream_xml() {
+    let lcov = "SF:src/a/x.rs\nDA:1,1\nDA:2,0\nBRDA:1,0,0,1\nBRDA:1,0,1,0\nFN:1,f\nFNDA:3,f\nend_of_record\nSF:src/a/y.rs\nDA:1,0\nend_of_record\nSF:src/b/z.rs\nDA:1,4\nDA:3,1\nend_of_record\nSF:gen/g.rs\nDA:1,0\nend_of_record\n";
+    let expected = parse_lines(lcov.as_bytes().lines(), ".", &["gen"]).unwrap();
+    let (xml, stats) = stream_xml(
+        lcov.as_bytes().lines(),
+        Cursor::new(Vec::new()),
+        ".",
+        &["gen"],
+        &ParseOptions::default(),
+        1_346_815_648_000,
+        NullDemangler::new(),
+        &XmlOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(
+        format!("{:?}", stats.summary),
+        format!("{:?}", expected.summary())
+    );
+    let parsed = parse_cobertura(xml.into_inner().as_slice(), &[]).unwrap();
+    assert_eq!(
+        format!("{:?}", parsed.summary()),
+        format!("{:?}", expected.summary())
+    );
+    for (name, package) in &expected.packages {
+        assert_eq!(
+            format!("{:?}", parsed.packages.get(name).unwrap().summary()),
+            format!("{:?}", package.summary())
+        );
+    }
+    assert!(!parsed.packages.contains_key("gen"));
+
+    // apart from the reserved space in the start tags the output equals the in-memory one
+    let lcov = "SF:src/a/x.rs\nDA:1,1\nDA:2,0\nFN:1,f\nFNDA:3,f\nend_of_record\n";
+    let result = parse_lines(lcov.as_bytes().lines(), ".", &[]).unwrap();
+    let xml = coverage_to_string(&result, 1_346_815_648_000, NullDemangler::new()).unwrap();
+    let mut buffer = Cursor::new(XML_HEADER.as_bytes().to_vec());
+    buffer.set_position(XML_HEADER.len() as u64);
+    let (streamed, _) = stream_xml(
+        lcov.as_bytes().lines(),
+        buffer,
+        ".",
+        &[],
+        &ParseOptions::default(),
+        1_346_815_648_000,
+        NullDemangler::new(),
+        &XmlOptions::default(),
+    )
+    .unwrap();
+    let streamed = String::from_utf8(streamed.into_inner()).unwrap();
+    let trailing_spaces = regex::Regex::new(" +>").unwrap();
+    assert_eq!(trailing_spaces.replace_all(&streamed, ">"), xml);
+
+    // records that would be written twice can't be streamed
+    let stream = |lcov: &str| {
+        stream_xml(
+            lcov.as_bytes().lines(),
+            Cursor::new(Vec::new()),
+            ".",
+            &[],
+            &ParseOptions::default(),
+            0,
+            NullDemangler::new(),
+            &XmlOptions::default(),
+        )
+        .map(drop)
+    };
+    // sorted by path, but `src` continues after `src.a`
+    let interleaved = "SF:src/a.rs\nDA:1,1\nend_of_record\nSF:src/a/x.rs\nDA:1,1\nend_of_record\nSF:src/b.rs\nDA:1,0\nend_of_record\n";
+    let error = stream(interleaved).unwrap_err().to_string();
+    assert!(
+        error.starts_with("package `src` continues after records of other packages"),
+        "{error}"
+    );
+    let repeated = "TN:unit\nSF:src/a.rs\nDA:1,1\nend_of_record\nTN:integration\nSF:src/a.rs\nDA:1,0\nDA:2,1\nend_of_record\n";
+    let error = stream(repeated).unwrap_err().to_string();
+    assert!(
+        error.starts_with("src/a.rs appears in several records"),
+        "{error}"
+    );
+    // both are merged without streaming
+    let result = parse_lines(repeated.as_bytes().lines(), ".", &[]).unwrap();
+    assert_eq!(result.packages.len(), 1);
+    assert_eq!(result.summary().lines_covered, 2);
+    let result = parse_lines(interleaved.as_bytes().lines(), ".", &[]).unwrap();
+    assert_eq!(result.packages.get("src").unwrap().classes.len(), 2);
+    stream("SF:src/a.rs\nDA:1,1\nend_of_record\nSF:src/b.rs\nDA:1,0\nend_of_record\nSF:src/a/x.rs\nDA:1,1\nend_of_record\n").unwrap();
+}
+
+#[test]
+fn test_auto_demangler() {
+    let symbols = [
+        (
+            "_ZN4core3ptr13drop_in_place17h1d0ac1e0a3f4ab0cE",
+            SymbolKind::Rust,
+            "core::ptr::drop_in_place::h1d0ac1e0a3f4ab0c",
+        ),
+        ("_RNvC6_123foo3bar", SymbolKind::Rust, "123foo::bar"),
+        ("_ZN3Foo3sqrEi", SymbolKind::Cpp, "Foo::sqr(int)"),
+        (
+            "_ZNK2ns6Widget4nameEv",
+            SymbolKind::Cpp,
+            "ns::Widget::name() const",
+        ),
+        ("_D4test3fooFiZv", SymbolKind::D, "void test.foo(int)"),
+        ("main", SymbolKind::Unknown, "main"),
+        ("(anonymous_1)", SymbolKind::Unknown, "(anonymous_1)"),
+    ];
+    #[cfg(target_os = "macos")]
+    let external = AutoDemangler::with_cpp_tool("/opt/homebrew/opt/binutils/bin/c++filt");
+    #[cfg(not(target_os = "macos"))]
+    let external = AutoDemangler::with_cpp_tool("c++filt");
+    for mut demangler in [AutoDemangler::new(), external] {
+        for (symbol, kind, demangled) in symbols {
+            assert_eq!(SymbolKind::detect(symbol), kind, "{symbol}");
+            assert_eq!(demangler.demangle(symbol).unwrap(), demangled);
+        }
+        demangler.stop().unwrap();
+    }
+    assert_eq!(SymbolKind::detect("$s4main5helloyyF"), SymbolKind::Swift);
+    assert_eq!(SymbolKind::detect("_Dmain"), SymbolKind::D);
+    // reports without C++ or Swift symbols don't start the external tools
+    let mut demangler = AutoDemangler::with_cpp_tool("/nonexistent/c++filt")
+        .with_swift(SwiftDemangler::with_tool("/nonexistent/swift-demangle"));
+    assert_eq!(
+        demangler.demangle("_RNvC6_123foo3bar").unwrap(),
+        "123foo::bar"
+    );
+    // a missing C++ tool keeps the names mangled
+    assert_eq!(
+        demangler.demangle("_ZN3Foo3sqrEi").unwrap(),
+        "_ZN3Foo3sqrEi"
+    );
+    demangler.prepare(&["_ZNK2ns6Widget4nameEv"]).unwrap();
+    assert_eq!(
+        demangler.demangle("_ZNK2ns6Widget4nameEv").unwrap(),
+        "_ZNK2ns6Widget4nameEv"
+    );
+    // as does a missing Swift tool, reported once
+    for _ in 0..2 {
+        assert_eq!(
+            demangler.demangle("$s4main5helloyyF").unwrap(),
+            "$s4main5helloyyF"
+        );
+    }
+    demangler.prepare(&["$s4main3fooyyF"]).unwrap();
+    demangler.stop().unwrap();
+    let diagnostics = demangler.take_diagnostics();
+    assert_eq!(diagnostics.len(), 2, "{diagnostics:?}");
+    assert!(diagnostics[0].starts_with("cannot start /nonexistent/c++filt: "));
+    assert!(diagnostics[0].ends_with(", keeping C++ names mangled"));
+    assert!(diagnostics[1].starts_with("cannot start /nonexistent/swift-demangle: "));
+    assert!(diagnostics[1].ends_with(", keeping Swift names mangled"));
+}
+
+#[test]
+fn test_d_demangler() {
+    let symbols = [
+        ("_Dmain", "D main"),
+        ("_D8demangle4testFaZv", "void demangle.test(char)"),
+        (
+            "_D8demangle4testFAyaZv",
+            "void demangle.test(immutable(char)[])",
+        ),
+        (
+            "_D8demangle4testFOxaZv",
+            "void demangle.test(shared(const(char)))",
+        ),
+        ("_D8demangle4testFHiAaZv", "void demangle.test(char[][int])"),
+        ("_D8demangle4testFG42aZv", "void demangle.test(char[42])"),
+        (
+            "_D8demangle4testFJaKaLaZv",
+            "void demangle.test(out char, ref char, lazy char)",
+        ),
+        (
+            "_D8demangle4testFaaYv",
+            "void demangle.test(char, char, ...)",
+        ),
+        ("_D8demangle4testFAiXv", "void demangle.test(int[]...)"),
+        (
+            "_D8demangle4testFPFiZvZv",
+            "void demangle.test(void function(int))",
+        ),
+        (
+            "_D8demangle4testFLC6ObjectLDFLiZiZi",
+            "int demangle.test(lazy Object, lazy int delegate(lazy int))",
+        ),
+        (
+            "_D8demangle4testFNaNbNfZv",
+            "pure nothrow @safe void demangle.test()",
+        ),
+        ("_D8demangle1S3fooMxFZi", "int demangle.S.foo() const"),
+        (
+            "_D8demangle4testFiZ5innerFZv",
+            "void demangle.test(int).inner()",
+        ),
+        ("_D4test1xi", "int test.x"),
+        // back references to an identifier and to a type
+        (
+            "_D3std5stdio__T7writelnTAyaZQnFNfQjZv",
+            "@safe void std.stdio.writeln!(immutable(char)[]).writeln(immutable(char)[])",
+        ),
+        ("_D4test__T3fooVii42ZQkFZv", "void test.foo!(42).foo()"),
+        (
+            "_D4test__T3fooVAyaa3_616263ZQsFZv",
+            "void test.foo!(\"abc\").foo()",
+        ),
+        // no D symbols, truncated or invalid back reference
+        ("_ZN3Foo3sqrEi", "_ZN3Foo3sqrEi"),
+        ("_D8demangle4testFa", "_D8demangle4testFa"),
+        ("_D4testFQzZv", "_D4testFQzZv"),
+    ];
+    let mut demangler = DDemangler::new();
+    for (symbol, demangled) in symbols {
+        assert_eq!(demangler.demangle(symbol).unwrap(), demangled, "{symbol}");
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_swift_demangler() {
+    let script = fake_demangler(
+        "swift-demangle",
+        r#"while read -r name; do
+    case "$name" in
+        '$s4main5helloyyF')
+            if [ "$1" = --simplified ]; then echo 'hello()'; else echo 'main.hello() -> ()'; fi ;;
+        *) echo "$name" ;;
+    esac
+done
+"#,
+    );
+    let tool = script.to_str().unwrap();
+    let mut demangler = SwiftDemangler::with_tool(tool);
+    demangler
+        .prepare(&["$s4main5helloyyF", "_ZN3Foo3sqrEi"])
+        .unwrap();
+    assert_eq!(
+        demangler.demangle("$s4main5helloyyF").unwrap(),
+        "main.hello() -> ()"
+    );
+    // not sent to the tool
+    assert_eq!(
+        demangler.demangle("_ZN3Foo3sqrEi").unwrap(),
+        "_ZN3Foo3sqrEi"
+    );
+    demangler.stop().unwrap();
+
+    let mut demangler = SwiftDemangler::with_tool(tool).simplified();
+    assert_eq!(demangler.demangle("$s4main5helloyyF").unwrap(), "hello()");
+    demangler.stop().unwrap();
+
+    let mut demangler = AutoDemangler::new().with_swift(SwiftDemangler::with_tool(tool));
+    assert_eq!(
+        demangler.demangle("$s4main5helloyyF").unwrap(),
+        "main.hello() -> ()"
+    );
+    assert_eq!(demangler.demangle("_D4test1xi").unwrap(), "int test.x");
+    demangler.stop().unwrap();
+    // the trailing return type stays in the signature
+    assert_eq!(
+        MethodNames::Split.apply("main.hello() -> ()"),
+        ("main.hello", "() -> ()")
+    );
+}
+
+/// Writes an executable shell script standing in for `c++filt`
+#[cfg(all(test, unix))]
+fn fake_demangler(name: &str, script: &str) -> std::path::PathBuf {
+    use std::os::unix::fs::PermissionsExt;
+    let dir = std::env::temp_dir().join(format!("lcov2cobertura-demangler-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    std::fs::write(&path, format!("#!/bin/sh\n{script}")).unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    path
+}
+
+#[cfg(unix)]
+#[test]
+fn test_cpp_demangler_protocol() {
+    let script = fake_demangler(
+        "misbehaving",
+        r#"while read -r name; do
+  case "$name" in
+    _Zswallow*) ;;
+    _Ztwice*) echo "first $name"; echo "second $name" ;;
+    _Z*) echo "demangled $name" ;;
+    *) echo "$name" ;;
+  esac
+done
+"#,
+    );
+    let mut demangler = CppDemangler::new(script.to_str().unwrap())
+        .unwrap()
+        .timeout(std::time::Duration::from_secs(5));
+    demangler.prepare(&["_Za", "plain", "_Zb", "_Za"]).unwrap();
+    assert_eq!(demangler.demangle("_Za").unwrap(), "demangled _Za");
+    assert_eq!(demangler.demangle("plain").unwrap(), "plain");
+    // the sentinel keeps later names aligned after swallowed or extra lines
+    assert_eq!(demangler.demangle("_Zswallow").unwrap(), "_Zswallow");
+    assert_eq!(demangler.demangle("_Zc").unwrap(), "demangled _Zc");
+    demangler.prepare(&["_Ztwice", "_Zd"]).unwrap();
+    assert_eq!(demangler.demangle("_Ztwice").unwrap(), "_Ztwice");
+    assert_eq!(demangler.demangle("_Zd").unwrap(), "demangled _Zd");
+    // answers of earlier batches are dropped, but sent again when needed
+    assert_eq!(demangler.demangle("_Za").unwrap(), "demangled _Za");
+    assert_eq!(
+        demangler.diagnostics().len(),
+        3,
+        "{:?}",
+        demangler.diagnostics()
+    );
+    assert!(demangler.diagnostics()[0].contains("answered 0 lines for 1 names"));
+    demangler.stop().unwrap();
+
+    let script = fake_demangler("hanging", "read -r name\nexec sleep 60\n");
+    let mut demangler = CppDemangler::new(script.to_str().unwrap())
+        .unwrap()
+        .timeout(std::time::Duration::from_millis(300));
+    let start = std::time::Instant::now();
+    assert_eq!(demangler.demangle("_Za").unwrap(), "_Za");
+    assert_eq!(demangler.demangle("_Zb").unwrap(), "_Zb");
+    assert!(start.elapsed() < std::time::Duration::from_secs(5));
+    assert!(demangler.diagnostics()[0].contains("no answer within 300ms"));
+    demangler.stop().unwrap();
+    // `auto` passes its timeout on to the C++ tool
+    let mut demangler = AutoDemangler::with_cpp_tool(script.to_str().unwrap())
+        .timeout(std::time::Duration::from_millis(300));
+    let start = std::time::Instant::now();
+    assert_eq!(
+        demangler.demangle("_ZN3Foo3sqrEi").unwrap(),
+        "_ZN3Foo3sqrEi"
+    );
+    assert!(start.elapsed() < std::time::Duration::from_secs(5));
+    demangler.stop().unwrap();
+    assert!(demangler.take_diagnostics()[0].contains("no answer within 300ms"));
+
+    let script = fake_demangler(
+        "crashing",
+        "read -r name\necho \"fake c++filt crashed on $name\" >&2\nexit 3\n",
+    );
+    let mut demangler = CppDemangler::new(script.to_str().unwrap()).unwrap();
+    assert_eq!(demangler.demangle("_Za").unwrap(), "_Za");
+    let diagnostic = &demangler.diagnostics()[0];
+    assert!(
+        diagnostic.contains("exited (exit status: 3)"),
+        "{diagnostic}"
+    );
+    assert!(
+        diagnostic.contains("fake c++filt crashed on _Za"),
+        "{diagnostic}"
+    );
+    demangler.stop().unwrap();
+    // returned instead of printed, once
+    assert_eq!(demangler.take_diagnostics().len(), 1);
+    assert!(demangler.take_diagnostics().is_empty());
+    std::fs::remove_dir_all(script.parent().unwrap()).unwrap();
+}
+
+/// Upper-cases names and counts how often it was asked
+#[cfg(test)]
+#[derive(Default)]
+struct CountingDemangler {
+    calls: std::rc::Rc<std::cell::Cell<usize>>,
+}
+
+#[cfg(test)]
+impl Demangler for CountingDemangler {
+    fn demangle<'a>(&mut self, ident: &'a str) -> std::io::Result<std::borrow::Cow<'a, str>> {
+        self.calls.set(self.calls.get() + 1);
+        Ok(ident.to_uppercase().into())
+    }
+}
+
+#[test]
+fn test_caching_demangler() {
+    let lcov = "SF:src/a.cpp\nFN:1,f\nFN:2,g\nFNDA:1,f\nend_of_record\nSF:src/b.cpp\nFN:1,f\nFN:5,g\nFN:9,h\nend_of_record\nSF:lib/c.cpp\nFN:3,f\nend_of_record\n";
+    let result = parse_lines(lcov.as_bytes().lines(), ".", &[]).unwrap();
+    assert_eq!(result.method_names(), ["f", "g", "h"]);
+    let counting = CountingDemangler::default();
+    let calls = counting.calls.clone();
+    let xml = coverage_to_string(&result, 0, CachingDemangler::new(counting)).unwrap();
+    assert_eq!(calls.get(), 3);
+    assert_eq!(xml.matches(r#"<method name="F""#).count(), 3);
+
+    let counting = CountingDemangler::default();
+    let calls = counting.calls.clone();
+    let mut demangler =
+        CachingDemangler::with_names(counting, [("f".to_string(), "F!".to_string())].into());
+    assert_eq!(demangler.demangle("f").unwrap(), "F!");
+    assert_eq!(demangler.demangle("g").unwrap(), "G");
+    assert_eq!(demangler.demangle("g").unwrap(), "G");
+    assert_eq!(calls.get(), 1);
+
+    // a bounded cache drops the names not used during two generations
+    let counting = CountingDemangler::default();
+    let calls = counting.calls.clone();
+    let mut demangler = CachingDemangler::bounded(counting, 2);
+    for ident in ["a", "b", "c", "a"] {
+        assert_eq!(demangler.demangle(ident).unwrap(), ident.to_uppercase());
+    }
+    // `a` moved from the previous generation to the current one
+    assert_eq!(calls.get(), 3);
+    demangler.demangle("d").unwrap();
+    demangler.prepare(&["a", "c", "b"]).unwrap();
+    assert_eq!(calls.get(), 5);
+    assert_eq!(demangler.demangle("b").unwrap(), "B");
+    assert_eq!(calls.get(), 5);
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn test_demangle_parallel() {
+    let names: Vec<String> = (0..100)
+        .map(|i| format!("bar{i}"))
+        .map(|name| format!("_RNvC6_123foo{}{name}", name.len()))
+        .collect();
+    let names: Vec<&str> = names.iter().map(String::as_str).collect();
+    let demangled = demangle_parallel(&names, || Ok(RustDemangler::new()), Some(3)).unwrap();
+    assert_eq!(demangled.len(), 100);
+    assert_eq!(demangled["_RNvC6_123foo5bar42"], "123foo::bar42");
+    assert!(demangle_parallel(&[], || Ok(RustDemangler::new()), Some(3))
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn test_demangler_kind() {
+    let options = DemanglerOptions::default();
+    let kinds = [
+        ("$null", DemanglerKind::Null, "_RNvC6_123foo3bar"),
+        ("$rust", DemanglerKind::Rust, "123foo::bar"),
+        ("auto", DemanglerKind::Auto, "123foo::bar"),
+        // the Swift tool isn't started for other symbols
+        ("$swift", DemanglerKind::Swift, "_RNvC6_123foo3bar"),
+        ("$d", DemanglerKind::D, "_RNvC6_123foo3bar"),
+    ];
+    for (name, kind, demangled) in kinds {
+        assert_eq!(name.parse::<DemanglerKind>().unwrap(), kind);
+        let mut demangler: Box<dyn Demangler> = kind.build(&options).unwrap();
+        assert_eq!(demangler.demangle("_RNvC6_123foo3bar").unwrap(), demangled);
+        demangler.stop().unwrap();
+    }
+    assert_eq!(
+        "c++filt".parse::<DemanglerKind>().unwrap(),
+        DemanglerKind::External("c++filt".to_string())
+    );
+    #[cfg(feature = "cpp-demangle")]
+    assert_eq!("$cpp".parse::<DemanglerKind>().unwrap(), DemanglerKind::Cpp);
+    #[cfg(not(feature = "cpp-demangle"))]
+    assert!("$cpp".parse::<DemanglerKind>().is_err());
+    assert!(DemanglerKind::External("/nonexistent/c++filt".to_string())
+        .build(&options)
+        .is_err());
+}
+
+#[test]
+fn test_split_signature() {
+    let names = [
+        ("Foo::sqr(int)", "Foo::sqr", "(int)"),
+        (
+            "ns::Widget::name(std::string const&) const",
+            "ns::Widget::name",
+            "(std::string const&) const",
+        ),
+        (
+            "void geo::clamp<float>(float, float, float)",
+            "geo::clamp<float>",
+            "(float, float, float)",
+        ),
+        (
+            "(anonymous namespace)::helper(unsigned long)",
+            "(anonymous namespace)::helper",
+            "(unsigned long)",
+        ),
+        (
+            "Foo::operator()(int) const",
+            "Foo::operator()",
+            "(int) const",
+        ),
+        (
+            "Foo::operator<(Foo const&)",
+            "Foo::operator<",
+            "(Foo const&)",
+        ),
+        (
+            "bool std::operator==<char>(A, B)",
+            "std::operator==<char>",
+            "(A, B)",
+        ),
+        (
+            "operator new[](unsigned long)",
+            "operator new[]",
+            "(unsigned long)",
+        ),
+        (
+            "Foo::operator std::string() const",
+            "Foo::operator std::string",
+            "() const",
+        ),
+        (
+            "ns::run()::{lambda(int)#1}::operator()(int) const",
+            "ns::run()::{lambda(int)#1}::operator()",
+            "(int) const",
+        ),
+        ("ns::f(int) [clone .cold]", "ns::f", "(int) [clone .cold]"),
+        (
+            "<alloc::vec::Vec<T> as core::fmt::Debug>::fmt",
+            "<alloc::vec::Vec<T> as core::fmt::Debug>::fmt",
+            "",
+        ),
+        ("(anonymous_1)", "(anonymous_1)", ""),
+        ("main", "main", ""),
+        (
+            "main.apply(f: (Swift.Int) -> ()) -> Swift.Int",
+            "main.apply",
+            "(f: (Swift.Int) -> ()) -> Swift.Int",
+        ),
+        (
+            "pure @safe void std.stdio.writeln!(immutable(char)[]).writeln(immutable(char)[])",
+            "std.stdio.writeln!(immutable(char)[]).writeln",
+            "(immutable(char)[])",
+        ),
+    ];
+    for (demangled, name, signature) in names {
+        assert_eq!(split_signature(demangled), (name, signature), "{demangled}");
+        assert_eq!(MethodNames::Short.apply(demangled), (name, ""));
+        assert_eq!(MethodNames::Full.apply(demangled), (demangled, ""));
+    }
+
+    let lcov = "SF:src/foo.cpp\nFN:3,_ZNK3Foo6answerEv\nFNDA:1,_ZNK3Foo6answerEv\nend_of_record\n";
+    let result = parse_lines(lcov.as_bytes().lines(), ".", &[]).unwrap();
+    let demangler = CachingDemangler::with_names(
+        NullDemangler::new(),
+        [(
+            "_ZNK3Foo6answerEv".to_string(),
+            "Foo::answer() const".to_string(),
+        )]
+        .into(),
+    );
+    let options = XmlOptions {
+        method_names: "split".parse().unwrap(),
+        ..XmlOptions::default()
+    };
+    let xml = dump_xml_with(Vec::new(), &result, 0, demangler, &options).unwrap();
+    let xml = String::from_utf8(xml).unwrap();
+    assert!(
+        xml.contains(r#"<method name="Foo::answer" signature="() const""#),
+        "{xml}"
+    );
+}
+
+#[test]
+fn test_rewrite_rules() {
+    let presets = [
+        (
+            RewritePreset::Rust,
+            "core::ptr::drop_in_place::h1d0ac1e0a3f4ab0c",
+            "core::ptr::drop_in_place",
+        ),
+        (RewritePreset::Rust, "foo::bar::{{closure}}", "foo::bar"),
+        (RewritePreset::Rust, "foo::bar::{closure#0}", "foo::bar"),
+        (RewritePreset::Rust, "foo::parse::<u32>", "foo::parse"),
+        (
+            RewritePreset::Rust,
+            "<std::collections::HashMap<K, Vec<Option<u8>>> as foo::Bar>::baz",
+            "<std::collections::HashMap<_> as foo::Bar>::baz",
+        ),
+        (
+            RewritePreset::Cpp,
+            "std::__1::vector<int, std::__1::allocator<int> >::push_back(int const&)",
+            "std::vector<int>::push_back(int const&)",
+        ),
+        (
+            RewritePreset::Cpp,
+            "greet(std::__cxx11::basic_string<char, std::char_traits<char>, std::allocator<char> > const&)",
+            "greet(std::string const&)",
+        ),
+        (
+            RewritePreset::Cpp,
+            "std::vector<std::__cxx11::basic_string<char, std::char_traits<char>, std::allocator<char> >, std::allocator<std::__cxx11::basic_string<char, std::char_traits<char>, std::allocator<char> > > >::vector()",
+            "std::vector<std::string>::vector()",
+        ),
+    ];
+    for (preset, name, rewritten) in presets {
+        let mut demangler = RewritingDemangler::new(NullDemangler::new(), preset.rules());
+        assert_eq!(demangler.demangle(name).unwrap(), rewritten);
+    }
+    assert_eq!("c++".parse::<RewritePreset>().unwrap(), RewritePreset::Cpp);
+    assert!("java".parse::<RewritePreset>().is_err());
+
+    let mut rules = RewritePreset::Rust.rules();
+    rules.push("^core::=c::".parse().unwrap());
+    let mut demangler = RewritingDemangler::new(RustDemangler::new(), rules);
+    assert_eq!(
+        demangler
+            .demangle("_ZN4core3ptr13drop_in_place17h1d0ac1e0a3f4ab0cE")
+            .unwrap(),
+        "c::ptr::drop_in_place"
+    );
+    // unchanged names are borrowed
+    let mut demangler = RewritingDemangler::new(NullDemangler::new(), RewritePreset::Cpp.rules());
+    assert!(matches!(
+        demangler.demangle("main").unwrap(),
+        std::borrow::Cow::Borrowed("main")
+    ));
+    assert!("no separator".parse::<RewriteRule>().is_err());
+    assert!("=empty pattern".parse::<RewriteRule>().is_err());
+    assert!("(=unbalanced".parse::<RewriteRule>().is_err());
+}
+
+/// Report with Rust and C++ instances of the same functions and their demangled names
+#[cfg(test)]
+fn instances_report() -> (String, HashMap<String, String>) {
+    use std::fmt::Write as _;
+
+    let functions = [
+        (
+            "_ZN5mycrate3foo17h1111111111111111E",
+            3,
+            1,
+            "mycrate::foo::h1111111111111111",
+        ),
+        (
+            "_ZN5mycrate3foo17h2222222222222222E",
+            3,
+            2,
+            "mycrate::foo::h2222222222222222",
+        ),
+        (
+            "_Z5clampIfET_S0_S0_S0_",
+            10,
+            0,
+            "float clamp<float>(float, float, float)",
+        ),
+        (
+            "_Z5clampIlET_S0_S0_S0_",
+            10,
+            4,
+            "long clamp<long>(long, long, long)",
+        ),
+        ("_Z3barv", 20, 1, "bar()"),
+        ("_Z3barv.cold", 22, 0, "bar() [clone .cold]"),
+    ];
+    let mut records = String::new();
+    for (name, line, hits, _) in &functions {
+        writeln!(records, "FN:{line},{name}\nFNDA:{hits},{name}").unwrap();
+    }
+    let names = functions
+        .iter()
+        .map(|(name, _, _, demangled)| ((*name).to_string(), (*demangled).to_string()))
+        .collect();
+    (
+        format!("SF:src/lib.rs\n{records}DA:3,1\nend_of_record\n"),
+        names,
+    )
+}
+
+#[test]
+fn test_group_methods() {
+    let (lcov, names) = instances_report();
+    let demangler = || CachingDemangler::with_names(NullDemangler::new(), names.clone());
+
+    let mut result = parse_lines(lcov.as_bytes().lines(), ".", &[]).unwrap();
+    result
+        .group_methods(MethodGrouping::Line, &mut demangler())
+        .unwrap();
+    let methods: Vec<(&str, usize, usize, usize)> = result
+        .methods()
+        .iter()
+        .map(|m| (m.name, m.line, m.hits, m.instances))
+        .collect();
+    assert_eq!(
+        methods,
+        [
+            ("_Z3barv", 20, 1, 1),
+            ("_Z3barv.cold", 22, 0, 1),
+            ("_Z5clampIfET_S0_S0_S0_", 10, 4, 2),
+            ("_ZN5mycrate3foo17h1111111111111111E", 3, 3, 2),
+        ]
+    );
+    assert_eq!(result.summary().functions_total, 4);
+    assert_eq!(result.summary().functions_covered, 3);
+
+    let mut result = parse_lines(lcov.as_bytes().lines(), ".", &[]).unwrap();
+    result
+        .group_methods(MethodGrouping::Name, &mut demangler())
+        .unwrap();
+    let methods: Vec<(&str, usize, usize, usize)> = result
+        .methods()
+        .iter()
+        .map(|m| (m.name, m.line, m.hits, m.instances))
+        .collect();
+    assert_eq!(
+        methods,
+        [
+            ("_Z3barv", 20, 1, 2),
+            ("_Z5clampIfET_S0_S0_S0_", 10, 0, 1),
+            ("_Z5clampIlET_S0_S0_S0_", 10, 4, 1),
+            ("_ZN5mycrate3foo17h1111111111111111E", 3, 3, 2),
+        ]
+    );
+    // grouping again changes nothing
+    result
+        .group_methods(MethodGrouping::Name, &mut demangler())
+        .unwrap();
+    assert_eq!(result.methods().len(), 4);
+
+    assert_eq!(
+        strip_hashes("mycrate::foo::h1111111111111111"),
+        "mycrate::foo"
+    );
+    assert_eq!(strip_hashes("mycrate::foo::hash"), "mycrate::foo::hash");
+    assert_eq!(
+        strip_hashes("f(int) [clone .isra.0] [clone .cold]"),
+        "f(int)"
+    );
+    assert!("both".parse::<MethodGrouping>().is_err());
+}
+
+#[test]
+fn test_group_methods_xml() {
+    let (lcov, names) = instances_report();
+    let demangler = || CachingDemangler::with_names(NullDemangler::new(), names.clone());
+    let options = XmlOptions {
+        method_grouping: MethodGrouping::Line,
+        ..XmlOptions::default()
+    };
+    let result = parse_lines(lcov.as_bytes().lines(), ".", &[]).unwrap();
+    let xml = dump_xml_with(Vec::new(), &result, 0, demangler(), &options).unwrap();
+    let xml = String::from_utf8(xml).unwrap();
+    assert_eq!(xml.matches("<method ").count(), 4);
+    assert!(xml.contains("name=\"float clamp&lt;float&gt;(float, float, float)\""));
+    // streamed classes are grouped before they are counted
+    let (_, stats) = stream_xml(
+        lcov.as_bytes().lines(),
+        Cursor::new(Vec::new()),
+        ".",
+        &[],
+        &ParseOptions::default(),
+        0,
+        demangler(),
+        &options,
+    )
+    .unwrap();
+    assert_eq!(stats.summary.functions_total, 4);
+}
+
+#[test]
+fn test_validate() {
+    let dir = std::env::temp_dir().join(format!("lcov2cobertura-validate-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("src")).unwrap();
+    std::fs::write(dir.join("src/ok.rs"), "fn a() {}\nfn b() {}\n").unwrap();
+    std::fs::write(dir.join("src/old.rs"), "fn a() {}\n").unwrap();
+    let d = dir.display();
+    let lcov = format!(
+        "SF:{d}/src/ok.rs\nFN:2,b\nDA:2,1\nend_of_record\n\
+         SF:{d}/src/old.rs\nFN:5,gone\nDA:1,1\nend_of_record\n\
+         SF:{d}/src/missing.rs\nDA:1,0\nend_of_record\n"
+    );
+    let result = parse_lines(lcov.as_bytes().lines(), &dir, &[]).unwrap();
+    let problems = validate(&result).unwrap();
+    let source = dir.to_str().unwrap().to_string();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(
+        problems,
+        [
+            Problem::MissingSource {
+                file_name: "src/missing.rs".to_string(),
+                source: source.clone(),
+            },
+            Problem::LineOutOfRange {
+                file_name: "src/old.rs".to_string(),
+                line: 5,
+                lines: 1,
+            },
+        ]
+    );
+    assert_eq!(
+        problems[1].to_string(),
+        "src/old.rs: line 5 is past the end of the file (1 lines)"
+    );
+
+    let result = parse_lines("".as_bytes().lines(), ".", &[]).unwrap();
+    assert_eq!(validate(&result).unwrap(), [Problem::NoSourceFiles]);
+}
+
+#[test]
+fn test_read_file_list() {
+    use std::path::PathBuf;
+
+    assert_eq!(
+        read_file_list(b"a.info\r\nb dir/b.info\n\n").unwrap(),
+        [PathBuf::from("a.info"), PathBuf::from("b dir/b.info")]
+    );
+    assert_eq!(
+        read_file_list(b"a.info\0with\nnewline.info\0").unwrap(),
+        [PathBuf::from("a.info"), PathBuf::from("with\nnewline.info")]
+    );
+    assert!(read_file_list(b"").unwrap().is_empty());
+    assert!(read_file_list(b"\xff.info\n").is_err());
+}
+
+#[test]
+fn test_expand_inputs() {
+    use std::path::PathBuf;
+
+    let dir = std::env::temp_dir().join(format!("lcov2cobertura-inputs-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("a/deep")).unwrap();
+    std::fs::create_dir_all(dir.join("b")).unwrap();
+    std::fs::create_dir_all(dir.join("empty")).unwrap();
+    for file in ["a/lcov.info", "a/deep/x.info", "a/notes.txt", "b/lcov.info"] {
+        std::fs::write(dir.join(file), "").unwrap();
+    }
+    let d = dir.display();
+    let expand = |inputs: &[String], search: &InputSearch| {
+        let inputs: Vec<PathBuf> = inputs.iter().map(PathBuf::from).collect();
+        expand_inputs(&inputs, search)
+    };
+    let search = InputSearch::default();
+    let recursive = InputSearch {
+        recursive: true,
+        ..InputSearch::default()
+    };
+    let globbed = expand(&[format!("{d}/*/lcov.info")], &search).unwrap();
+    let dirs = expand(&[format!("{d}/a"), format!("{d}/b")], &search).unwrap();
+    let deep = expand(&[format!("{d}/a")], &recursive).unwrap();
+    let any_depth = expand(
+        &[format!("{d}/**/*.info"), format!("{d}/b/lcov.info")],
+        &search,
+    );
+    let kept = expand(&["-".to_string(), format!("{d}/missing.info")], &search).unwrap();
+    let no_match = expand(&[format!("{d}/*/*.lcov")], &search);
+    let empty = expand(&[format!("{d}/empty")], &recursive);
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(globbed, [dir.join("a/lcov.info"), dir.join("b/lcov.info")]);
+    assert_eq!(dirs, globbed);
+    assert_eq!(deep, [dir.join("a/deep/x.info"), dir.join("a/lcov.info")]);
+    assert_eq!(
+        any_depth.unwrap(),
+        [
+            dir.join("a/deep/x.info"),
+            dir.join("a/lcov.info"),
+            dir.join("b/lcov.info")
+        ]
+    );
+    assert_eq!(kept, [PathBuf::from("-"), dir.join("missing.info")]);
+    assert_eq!(
+        no_match.unwrap_err().to_string(),
+        format!("no input files match {d}/*/*.lcov")
+    );
+    assert_eq!(
+        empty.unwrap_err().to_string(),
+        format!("no files named *.info in {d}/empty")
+    );
+}
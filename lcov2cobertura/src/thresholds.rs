@@ -0,0 +1,137 @@
+//! Minimum coverage checks against the computed [`Summary`] values
+use std::fmt;
+
+use crate::{CompSummary, CoverageData, Metric, Summary};
+
+/// Minimum coverage in percent (0 to 100) per metric, `None` disables the check
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Thresholds {
+    /// minimum line coverage
+    pub lines: Option<f64>,
+    /// minimum branch coverage
+    pub branches: Option<f64>,
+    /// minimum function coverage
+    pub functions: Option<f64>,
+}
+
+impl Thresholds {
+    fn get(&self, metric: Metric) -> Option<f64> {
+        match metric {
+            Metric::Lines => self.lines,
+            Metric::Branches => self.branches,
+            Metric::Functions => self.functions,
+        }
+    }
+
+    /// Checks the summary, metrics without anything to measure (e.g. no branches) always pass
+    fn check(&self, scope: &str, summary: &Summary, failures: &mut Vec<ThresholdFailure>) {
+        for metric in Metric::ALL {
+            let Some(required) = self.get(metric) else {
+                continue;
+            };
+            let (_, total) = summary.counts(metric);
+            let actual = summary.rate(metric) * 100.;
+            if total > 0 && actual < required {
+                failures.push(ThresholdFailure {
+                    scope: scope.to_string(),
+                    metric,
+                    actual,
+                    required,
+                });
+            }
+        }
+    }
+}
+
+/// What a [`ThresholdRule`] pattern is matched against
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThresholdScope {
+    /// dotted package name
+    Package,
+    /// relative source file name
+    File,
+}
+
+/// Thresholds for all packages or files whose name matches the regex `pattern`
+#[derive(Debug, Clone)]
+pub struct ThresholdRule {
+    /// what the pattern is matched against
+    pub scope: ThresholdScope,
+    /// regular expression
+    pub pattern: String,
+    /// minimum coverage of every match
+    pub thresholds: Thresholds,
+}
+
+/// A package, file or the total coverage being below its threshold
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThresholdFailure {
+    /// `total`, `package <name>` or `file <name>`
+    pub scope: String,
+    /// failing metric
+    pub metric: Metric,
+    /// actual coverage in percent
+    pub actual: f64,
+    /// required coverage in percent
+    pub required: f64,
+}
+
+impl fmt::Display for ThresholdFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} coverage {:.2}% is below {:.2}%",
+            self.scope,
+            self.metric.name(),
+            self.actual,
+            self.required
+        )
+    }
+}
+
+/// Checks the total coverage against `total` and every matching package or file against the
+/// rules, returns all failures, sorted by scope.
+///
+/// # Errors
+///
+/// Invalid regular expression in a rule.
+pub fn check_thresholds(
+    cov_data: &CoverageData,
+    total: &Thresholds,
+    rules: &[ThresholdRule],
+) -> anyhow::Result<Vec<ThresholdFailure>> {
+    let mut failures = vec![];
+    total.check("total", &cov_data.summary(), &mut failures);
+
+    let mut rule_failures = vec![];
+    for rule in rules {
+        let re = regex::Regex::new(&rule.pattern)?;
+        for (pkg_name, package) in &cov_data.packages {
+            match rule.scope {
+                ThresholdScope::Package if re.is_match(pkg_name) => {
+                    rule.thresholds.check(
+                        &format!("package {pkg_name}"),
+                        &package.summary(),
+                        &mut rule_failures,
+                    );
+                }
+                ThresholdScope::File => {
+                    for (key, class) in &package.classes {
+                        let file_name = cov_data.class_path(key);
+                        if re.is_match(&file_name) {
+                            rule.thresholds.check(
+                                &format!("file {file_name}"),
+                                &class.summary(),
+                                &mut rule_failures,
+                            );
+                        }
+                    }
+                }
+                ThresholdScope::Package => (),
+            }
+        }
+    }
+    rule_failures.sort_by(|a, b| a.scope.cmp(&b.scope));
+    failures.extend(rule_failures);
+    Ok(failures)
+}
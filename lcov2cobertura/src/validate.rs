@@ -0,0 +1,98 @@
+//! Consistency checks of a parsed report against the source files on disk
+use std::fmt;
+use std::path::Path;
+
+use crate::CoverageData;
+
+/// A problem of a report found by [`validate`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    /// the report has no source files, e.g. all were filtered
+    NoSourceFiles,
+    /// a source file does not exist below its source root
+    MissingSource {
+        /// relative source file name
+        file_name: String,
+        /// source root the file name is relative to
+        source: String,
+    },
+    /// a line or function is past the end of its source file, the report is probably older
+    /// than the source
+    LineOutOfRange {
+        /// relative source file name
+        file_name: String,
+        /// highest line number of the report
+        line: usize,
+        /// number of lines of the source file
+        lines: usize,
+    },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoSourceFiles => write!(f, "no source files"),
+            Self::MissingSource { file_name, source } => {
+                write!(f, "{file_name}: not found in {source}")
+            }
+            Self::LineOutOfRange {
+                file_name,
+                line,
+                lines,
+            } => write!(
+                f,
+                "{file_name}: line {line} is past the end of the file ({lines} lines)"
+            ),
+        }
+    }
+}
+
+/// Checks that every source file of the report exists and is long enough for its lines and
+/// functions, problems are sorted by file name
+///
+/// # Errors
+///
+/// IO errors reading a source file other than it not existing
+pub fn validate(cov_data: &CoverageData) -> std::io::Result<Vec<Problem>> {
+    let mut classes: Vec<(&String, &crate::Class)> = cov_data
+        .packages
+        .values()
+        .flat_map(|package| package.classes.iter())
+        .map(|((_, file_name), class)| (file_name, class))
+        .collect();
+    if classes.is_empty() {
+        return Ok(vec![Problem::NoSourceFiles]);
+    }
+    classes.sort_unstable_by_key(|(file_name, class)| (*file_name, class.source));
+    let mut problems = vec![];
+    for (file_name, class) in classes {
+        let source = cov_data.source_of(class);
+        let content = match std::fs::read(Path::new(source).join(file_name)) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                problems.push(Problem::MissingSource {
+                    file_name: file_name.clone(),
+                    source: source.to_string(),
+                });
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        let lines = content.split(|c| *c == b'\n').count() - usize::from(content.ends_with(b"\n"));
+        let line = class
+            .lines
+            .keys()
+            .chain(class.methods.values().map(|(line, _)| line))
+            .copied()
+            .max()
+            .unwrap_or(0);
+        if line > lines {
+            problems.push(Problem::LineOutOfRange {
+                file_name: file_name.clone(),
+                line,
+                lines,
+            });
+        }
+    }
+    Ok(problems)
+}
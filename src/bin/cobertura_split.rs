@@ -1,4 +1,4 @@
-//! executable to efficiently split huge cobertura XML files
+//! executable to efficiently split huge cobertura XML files, same as `lcov2xml split`
 use std::path::PathBuf;
 
 use clap::Parser;
@@ -0,0 +1,554 @@
+//! Default arguments read from `lcov2xml.toml` or the `[package.metadata.lcov2xml]` table of a
+//! `Cargo.toml`, keys are the long option names and options given on the command line win.
+//! Top-level keys apply to the conversion and to the options of the same name of the other
+//! subcommands, except `output`, a `[command.<name>]` table to a single subcommand.
+use std::ffi::OsString;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use clap::{ArgAction, ArgMatches, Command, CommandFactory, FromArgMatches};
+
+use crate::{Cli, ConfigArgs, PackageMapFile, RewriteFile, ThresholdsFile};
+
+/// Config file looked up in the base directory and its parents
+const CONFIG_FILE: &str = "lcov2xml.toml";
+
+/// Contents of the files of `--package-map`, `--rewrite-rules` and `--thresholds`, given as
+/// tables of the config file instead of paths
+#[derive(Debug, Default, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct InlineTables {
+    pub(crate) package_map: Option<PackageMapFile>,
+    pub(crate) rewrite_rules: Option<RewriteFile>,
+    pub(crate) thresholds: Option<ThresholdsFile>,
+}
+
+impl InlineTables {
+    fn set(&mut self, id: &str, table: toml::Table) -> anyhow::Result<()> {
+        let value = toml::Value::Table(table);
+        match id {
+            "package_map" => self.package_map = Some(value.try_into()?),
+            "rewrite_rules" => self.rewrite_rules = Some(value.try_into()?),
+            "thresholds" => self.thresholds = Some(value.try_into()?),
+            _ => anyhow::bail!("`{id}` takes no table"),
+        }
+        Ok(())
+    }
+}
+
+/// Settings of a config file
+struct Config {
+    path: PathBuf,
+    settings: toml::Table,
+}
+
+impl Config {
+    /// Reads the settings of `path`, `None` for a `Cargo.toml` without `lcov2xml` metadata
+    fn read(path: &Path) -> anyhow::Result<Option<Self>> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("cannot read config {}: {e}", path.display()))?;
+        let table: toml::Table =
+            toml::from_str(&content).map_err(|e| anyhow::anyhow!("{}: {e}", path.display()))?;
+        let settings = if path.file_name() == Some("Cargo.toml".as_ref()) {
+            let metadata = ["package", "workspace"].iter().find_map(|section| {
+                table
+                    .get(*section)?
+                    .get("metadata")?
+                    .get("lcov2xml")?
+                    .as_table()
+            });
+            match metadata {
+                Some(metadata) => metadata.clone(),
+                None => return Ok(None),
+            }
+        } else {
+            table
+        };
+        Ok(Some(Self {
+            path: path.to_path_buf(),
+            settings,
+        }))
+    }
+
+    /// The first `lcov2xml.toml` or `Cargo.toml` with `lcov2xml` metadata in `dir` or its parents
+    fn find(dir: &Path) -> anyhow::Result<Option<Self>> {
+        for dir in std::path::absolute(dir)?.ancestors() {
+            let path = dir.join(CONFIG_FILE);
+            if path.is_file() {
+                return Self::read(&path);
+            }
+            let path = dir.join("Cargo.toml");
+            if path.is_file() {
+                if let Some(config) = Self::read(&path)? {
+                    return Ok(Some(config));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// argument of `command` set by `key`
+    fn find_arg<'a>(&self, command: &'a Command, key: &str) -> anyhow::Result<&'a clap::Arg> {
+        let long = key.replace('_', "-");
+        let Some(arg) = command.get_arguments().find(|arg| setting_key(arg) == long) else {
+            anyhow::bail!("unknown setting `{key}` in {}", self.path.display());
+        };
+        if ["config", "print_config"].contains(&arg.get_id().as_str()) {
+            anyhow::bail!("`{key}` cannot be set in {}", self.path.display());
+        }
+        Ok(arg)
+    }
+
+    /// Settings of `subcommand`, `None` for the conversion without a subcommand
+    fn settings_for(
+        &self,
+        cli_command: &Command,
+        subcommand: Option<&str>,
+    ) -> anyhow::Result<toml::Table> {
+        let name = subcommand.unwrap_or("convert");
+        let mut settings = toml::Table::new();
+        let mut tables = &toml::Table::new();
+        for (key, value) in &self.settings {
+            if key == "command" {
+                tables = value.as_table().ok_or_else(|| {
+                    anyhow::anyhow!("`command` in {} is no table", self.path.display())
+                })?;
+                continue;
+            }
+            let arg = self.find_arg(cli_command, key)?;
+            if name == "convert" || arg.get_id() != "output" {
+                settings.insert(setting_key(arg), value.clone());
+            }
+        }
+        for (table_name, table) in tables {
+            let command = cli_command
+                .find_subcommand(table_name)
+                .filter(|command| command.get_arguments().any(|arg| arg.get_id() == "config"))
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "no subcommand with settings `{table_name}` in {}",
+                        self.path.display()
+                    )
+                })?;
+            let table = table.as_table().ok_or_else(|| {
+                anyhow::anyhow!(
+                    "`command.{table_name}` in {} is no table",
+                    self.path.display()
+                )
+            })?;
+            for (key, value) in table {
+                let arg = self.find_arg(command, key)?;
+                if table_name == name {
+                    settings.insert(setting_key(arg), value.clone());
+                }
+            }
+        }
+        Ok(settings)
+    }
+
+    /// Command line arguments of the settings for `command`, tables of file options go to
+    /// `inline`
+    fn to_args(
+        &self,
+        settings: &toml::Table,
+        command: &Command,
+        matches: &ArgMatches,
+        inline: &mut InlineTables,
+    ) -> anyhow::Result<Vec<OsString>> {
+        // required arguments may come from the command line
+        let lenient = without_required(command);
+        let mut args = vec![];
+        for (key, value) in settings {
+            let Some(arg) = command.get_arguments().find(|arg| setting_key(arg) == *key) else {
+                continue;
+            };
+            let id = arg.get_id().as_str();
+            if given_on_command_line(command, matches, arg) {
+                continue;
+            }
+            let mut values = vec![OsString::from(command.get_name())];
+            let result = match value {
+                toml::Value::Table(table) => inline.set(id, table.clone()),
+                toml::Value::Array(array) => array
+                    .iter()
+                    .try_for_each(|value| push_value(&mut values, arg, value)),
+                value => push_value(&mut values, arg, value),
+            }
+            .and_then(|()| {
+                // clap errors don't name the argument without its `error-context` feature
+                lenient
+                    .clone()
+                    .try_get_matches_from(&values)
+                    .map(drop)
+                    .map_err(|e| anyhow::anyhow!("{}", e.kind()))
+            });
+            result
+                .map_err(|e| anyhow::anyhow!("setting `{key}` in {}: {e}", self.path.display()))?;
+            args.extend(values.into_iter().skip(1));
+        }
+        Ok(args)
+    }
+}
+
+/// `command` with all arguments of it and its subcommands optional
+fn without_required(command: &Command) -> Command {
+    command
+        .clone()
+        .mut_args(|arg| arg.required(false))
+        .mut_subcommands(|command| command.mut_args(|arg| arg.required(false)))
+}
+
+/// key of `arg` in config files, the long option name or `files` for the input files
+fn setting_key(arg: &clap::Arg) -> String {
+    arg.get_long()
+        .map_or_else(|| arg.get_id().to_string(), str::to_string)
+}
+
+/// whether `arg` or an argument conflicting with it was given on the command line
+fn given_on_command_line(command: &Command, matches: &ArgMatches, arg: &clap::Arg) -> bool {
+    let given = |arg: &clap::Arg| {
+        matches.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine)
+    };
+    given(arg)
+        || command.get_arg_conflicts_with(arg).into_iter().any(given)
+        || command.get_arguments().any(|other| {
+            given(other)
+                && command
+                    .get_arg_conflicts_with(other)
+                    .iter()
+                    .any(|conflict| conflict.get_id() == arg.get_id())
+        })
+}
+
+/// appends a single value of `arg` as it would be written on the command line
+fn push_value(
+    args: &mut Vec<OsString>,
+    arg: &clap::Arg,
+    value: &toml::Value,
+) -> anyhow::Result<()> {
+    if !arg.get_action().takes_values() {
+        match value {
+            toml::Value::Boolean(true) => args.push(format!("--{}", setting_key(arg)).into()),
+            toml::Value::Boolean(false) => (),
+            _ => anyhow::bail!("expected true or false, found {value}"),
+        }
+        return Ok(());
+    }
+    let value = match value {
+        toml::Value::String(value) => value.clone(),
+        toml::Value::Integer(value) => value.to_string(),
+        toml::Value::Float(value) => value.to_string(),
+        toml::Value::Boolean(value) => value.to_string(),
+        _ => anyhow::bail!("expected a string or number, found {value}"),
+    };
+    match arg.get_long() {
+        Some(long) => args.push(format!("--{long}={value}").into()),
+        None => args.push(value.into()),
+    }
+    Ok(())
+}
+
+/// Parses the command line and fills the options not given there from `--config` or the config
+/// file found from the base directory. Returns the arguments together with the command and the
+/// matches of the chosen subcommand. Usage errors, `--help` and `--version` exit like clap does.
+pub(crate) fn parse_args() -> anyhow::Result<(Cli, Command, ArgMatches)> {
+    parse_from(std::env::args_os().collect()).map_err(|e| match e.downcast::<clap::Error>() {
+        Ok(e) => e.exit(),
+        Err(e) => e,
+    })
+}
+
+/// [`parse_args`] of the command line `cli`, clap errors are returned as they are
+fn parse_from(cli: Vec<OsString>) -> anyhow::Result<(Cli, Command, ArgMatches)> {
+    let cli_command = Cli::command();
+    let (matches, missing) = match cli_command.clone().try_get_matches_from(&cli) {
+        // required arguments may come from the config file, checked once it is merged
+        Err(e) if e.kind() == ErrorKind::MissingRequiredArgument => (
+            without_required(&cli_command).try_get_matches_from(&cli)?,
+            Some(e),
+        ),
+        matches => (matches?, None),
+    };
+    let subcommand = matches.subcommand_name().map(str::to_string);
+    let command = match &subcommand {
+        Some(name) => cli_command.find_subcommand(name).cloned(),
+        None => Some(cli_command.clone()),
+    }
+    .ok_or_else(|| anyhow::anyhow!("unknown subcommand"))?;
+    let chosen_matches = |matches: &ArgMatches| {
+        matches
+            .subcommand()
+            .map_or_else(|| matches.clone(), |(_, matches)| matches.clone())
+    };
+    let command_matches = chosen_matches(&matches);
+
+    let config = if command.get_arguments().all(|arg| arg.get_id() != "config") {
+        None
+    } else if let Some(path) = command_matches.get_one::<PathBuf>("config") {
+        Some(Config::read(path)?.ok_or_else(|| {
+            anyhow::anyhow!("no [package.metadata.lcov2xml] table in {}", path.display())
+        })?)
+    } else {
+        // `merge` has no base directory
+        let base_dir = command_matches
+            .try_get_one::<PathBuf>("base_dir")
+            .ok()
+            .flatten()
+            .map_or(Path::new("."), PathBuf::as_path);
+        Config::find(base_dir)?
+    };
+    let Some(config) = config else {
+        if let Some(missing) = missing {
+            return Err(missing.into());
+        }
+        return Ok((Cli::from_arg_matches(&matches)?, command, command_matches));
+    };
+
+    let settings = config.settings_for(&cli_command, subcommand.as_deref())?;
+    let mut inline = InlineTables::default();
+    let mut config_args = vec![OsString::from(command.get_name())];
+    config_args.extend(config.to_args(&settings, &command, &command_matches, &mut inline)?);
+    // errors of the settings alone point at the config file
+    without_required(&command)
+        .try_get_matches_from(&config_args)
+        .map_err(|e| anyhow::anyhow!("{}: {}", config.path.display(), e.kind()))?;
+    // the settings go right after the subcommand, which has to be the first argument
+    let prefix = 1 + usize::from(subcommand.is_some());
+    let mut args: Vec<OsString> = cli.iter().take(prefix).cloned().collect();
+    args.extend(config_args.into_iter().skip(1));
+    args.extend(cli.into_iter().skip(prefix));
+    let matches = cli_command.try_get_matches_from(args)?;
+    let mut parsed = Cli::from_arg_matches(&matches)?;
+    if let Some(args) = parsed.settings_mut() {
+        args.inline = inline;
+        args.config_file = Some(config.path);
+    }
+    Ok((parsed, command, chosen_matches(&matches)))
+}
+
+/// The effective settings of `--print-config` for `command`, written as a config file
+pub(crate) fn render(
+    args: &ConfigArgs,
+    command: &Command,
+    matches: &ArgMatches,
+) -> anyhow::Result<String> {
+    let mut settings = toml::Table::try_from(&args.inline)?;
+    for arg in command.get_arguments() {
+        let id = arg.get_id().as_str();
+        let key = setting_key(arg);
+        if ["config", "print_config"].contains(&id) || settings.contains_key(&key) {
+            continue;
+        }
+        let value = if matches!(arg.get_action(), ArgAction::SetTrue) {
+            toml::Value::Boolean(matches.get_flag(id))
+        } else {
+            let Some(raw) = matches.get_raw(id) else {
+                continue;
+            };
+            let mut values =
+                raw.map(|value| toml::Value::String(value.to_string_lossy().into_owned()));
+            match arg.get_action() {
+                ArgAction::Append => toml::Value::Array(values.collect()),
+                _ => match values.next() {
+                    Some(value) => value,
+                    None => continue,
+                },
+            }
+        };
+        settings.insert(key, value);
+    }
+    let mut output = String::new();
+    if let Some(path) = &args.config_file {
+        writeln!(output, "# settings of {}", path.display())?;
+    }
+    output.push_str(&toml::to_string(&settings)?);
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Command as Subcommand;
+
+    /// empty scratch directory of a test
+    fn scratch(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("lcov2xml-config-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn parse(args: &[&str]) -> anyhow::Result<(Cli, Command, ArgMatches)> {
+        parse_from(
+            std::iter::once("lcov2xml")
+                .chain(args.iter().copied())
+                .map(OsString::from)
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_command_line_over_config() {
+        let dir = scratch("precedence");
+        let config = dir.join(CONFIG_FILE);
+        std::fs::write(
+            &config,
+            "excludes = \"from-config\"\nverbose = true\noutput = \"config.xml\"\n",
+        )
+        .unwrap();
+        let config = config.to_str().unwrap();
+        let (cli, _, _) = parse(&["--config", config, "-e", "from-cli", "a.info"]).unwrap();
+        assert_eq!(cli.convert.parse.excludes, "from-cli");
+        assert!(cli.convert.input.verbose);
+        assert_eq!(cli.convert.output, Path::new("config.xml"));
+        assert_eq!(cli.convert.input.files, [Path::new("a.info")]);
+        assert_eq!(
+            cli.convert.settings.config_file.as_deref(),
+            Some(Path::new(config))
+        );
+        // `output` of the top level only applies to the conversion
+        let (cli, _, _) = parse(&["merge", "--config", config, "a.info"]).unwrap();
+        let Some(Subcommand::Merge(args)) = cli.command else {
+            panic!("no merge command");
+        };
+        assert!(args.output.output.is_none());
+        assert!(args.input.verbose);
+
+        std::fs::write(dir.join(CONFIG_FILE), "no-such-option = 1\n").unwrap();
+        let error = parse(&["--config", config, "a.info"]).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("unknown setting `no-such-option`"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_command_tables() {
+        let dir = scratch("tables");
+        let config = dir.join(CONFIG_FILE);
+        std::fs::write(
+            &config,
+            "excludes = \"all\"\n[command.diff]\ndiff = \"mr.diff\"\nexcludes = \"diff\"\n",
+        )
+        .unwrap();
+        let config = config.to_str().unwrap();
+        // a required argument may come from the config file
+        let (cli, _, _) = parse(&["diff", "--config", config, "a.info"]).unwrap();
+        let Some(Subcommand::Diff(args)) = cli.command else {
+            panic!("no diff command");
+        };
+        assert_eq!(args.diff, Path::new("mr.diff"));
+        assert_eq!(args.parse.excludes, "diff");
+        let (cli, _, _) = parse(&["diff", "--config", config, "--diff", "b.diff"]).unwrap();
+        let Some(Subcommand::Diff(args)) = cli.command else {
+            panic!("no diff command");
+        };
+        assert_eq!(args.diff, Path::new("b.diff"));
+        // tables of other subcommands are left out
+        let (cli, _, _) = parse(&["--config", config, "a.info"]).unwrap();
+        assert_eq!(cli.convert.parse.excludes, "all");
+        assert!(cli.convert.diff.is_none());
+
+        std::fs::write(dir.join(CONFIG_FILE), "excludes = \"all\"\n").unwrap();
+        let error = parse(&["diff", "--config", config, "a.info"]).unwrap_err();
+        assert_eq!(
+            error.downcast::<clap::Error>().unwrap().kind(),
+            ErrorKind::MissingRequiredArgument
+        );
+        std::fs::write(dir.join(CONFIG_FILE), "[command.split]\nfilename = \"a\"\n").unwrap();
+        let error = parse(&["--config", config, "a.info"]).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("no subcommand with settings `split`"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_metadata_discovery() {
+        let dir = scratch("metadata");
+        let base_dir = dir.join("crates").join("core");
+        std::fs::create_dir_all(&base_dir).unwrap();
+        std::fs::write(
+            dir.join("Cargo.toml"),
+            "[workspace]\n[workspace.metadata.lcov2xml]\nexcludes = \"workspace\"\n",
+        )
+        .unwrap();
+        // a manifest without `lcov2xml` metadata is skipped
+        std::fs::write(base_dir.join("Cargo.toml"), "[package]\nname = \"core\"\n").unwrap();
+        let base = base_dir.to_str().unwrap();
+        let (cli, _, _) = parse(&["-b", base, "a.info"]).unwrap();
+        assert_eq!(cli.convert.parse.excludes, "workspace");
+        assert_eq!(
+            cli.convert.settings.config_file,
+            Some(std::path::absolute(dir.join("Cargo.toml")).unwrap())
+        );
+
+        // `lcov2xml.toml` comes first in the same directory
+        std::fs::write(
+            base_dir.join("Cargo.toml"),
+            "[package]\nname = \"core\"\n[package.metadata.lcov2xml]\nexcludes = \"package\"\n",
+        )
+        .unwrap();
+        let (cli, _, _) = parse(&["-b", base, "a.info"]).unwrap();
+        assert_eq!(cli.convert.parse.excludes, "package");
+        std::fs::write(base_dir.join(CONFIG_FILE), "excludes = \"file\"\n").unwrap();
+        let (cli, _, _) = parse(&["-b", base, "a.info"]).unwrap();
+        assert_eq!(cli.convert.parse.excludes, "file");
+
+        let manifest = dir.join("Cargo.toml");
+        std::fs::write(&manifest, "[workspace]\n").unwrap();
+        let error = parse(&["--config", manifest.to_str().unwrap(), "a.info"]).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("no [package.metadata.lcov2xml] table"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_print_config() {
+        let dir = scratch("print");
+        let config = dir.join(CONFIG_FILE);
+        std::fs::write(
+            &config,
+            "excludes = \"from-config\"\ninclude = [\"src/**\"]\n[package-map.packages]\nsrc = \"core\"\n",
+        )
+        .unwrap();
+        let config = config.to_str().unwrap();
+        let (mut cli, command, matches) = parse(&[
+            "--config",
+            config,
+            "-e",
+            "from-cli",
+            "--print-config",
+            "a.info",
+        ])
+        .unwrap();
+        let settings = cli.settings_mut().unwrap();
+        assert!(settings.print_config);
+        let output = render(settings, &command, &matches).unwrap();
+        assert!(output.starts_with(&format!("# settings of {config}\n")));
+        let printed: toml::Table = toml::from_str(&output).unwrap();
+        assert_eq!(printed["excludes"].as_str(), Some("from-cli"));
+        assert_eq!(printed["include"].as_array().unwrap().len(), 1);
+        assert_eq!(
+            printed["package-map"]["packages"]["src"].as_str(),
+            Some("core")
+        );
+        assert_eq!(
+            printed["files"].as_array().unwrap()[0].as_str(),
+            Some("a.info")
+        );
+        assert_eq!(printed["verbose"].as_bool(), Some(false));
+        assert!(!printed.contains_key("config"));
+        assert!(!printed.contains_key("print-config"));
+        // the printed settings read back to the same arguments
+        std::fs::write(dir.join(CONFIG_FILE), &output).unwrap();
+        let (cli, _, _) = parse(&["--config", config]).unwrap();
+        assert_eq!(cli.convert.parse.excludes, "from-cli");
+        assert_eq!(cli.convert.input.files, [Path::new("a.info")]);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
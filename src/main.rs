@@ -1,101 +1,1164 @@
-//! executable to run the conversion
+//! executable to run the conversion and the other subcommands
 
 use clap::Parser;
-use std::io::{BufRead, Read};
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::io::{BufRead, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 use std::time::SystemTime;
 
 use lcov2cobertura as lcov2xml;
 
-/// Command line arguments
+mod config;
+#[cfg(test)]
+mod tests;
+
+/// Command line arguments, without a subcommand the input files are converted like by `convert`
 #[derive(Parser, Debug)]
-#[clap(author, version, about, long_about = None)]
-struct Args {
-    /// LCOV input files, use single dash '-' argument to read from standard input
+#[clap(
+    author,
+    version,
+    about,
+    long_about = None,
+    args_conflicts_with_subcommands = true,
+    after_help = EXIT_CODES
+)]
+struct Cli {
+    #[clap(subcommand)]
+    command: Option<Command>,
+    #[clap(flatten)]
+    convert: ConvertArgs,
+}
+
+/// Subcommands
+#[derive(clap::Subcommand, Debug)]
+enum Command {
+    /// Convert LCOV files to cobertura XML, the default without a subcommand
+    #[clap(after_help = EXIT_CODES)]
+    Convert(ConvertArgs),
+    /// Split a cobertura XML file into 9.5 megabytes big chunks for GitLab, keeps the original
+    Split(SplitArgs),
+    /// Merge LCOV files into one tracefile
+    Merge(MergeArgs),
+    /// Print a per-package coverage summary table of LCOV files and check thresholds
+    #[clap(after_help = EXIT_CODES)]
+    Summary(SummaryArgs),
+    /// Print the coverage of the lines changed in a unified diff
+    #[clap(after_help = EXIT_CODES)]
+    Diff(DiffArgs),
+    /// Compare the coverage of two reports and list regressions
+    #[clap(after_help = EXIT_CODES)]
+    Compare(CompareArgs),
+    /// Check that LCOV files parse and match the source files on disk
+    Validate(ValidateArgs),
+}
+
+impl Cli {
+    /// config file arguments of the chosen command, `None` for commands without a config file
+    fn settings_mut(&mut self) -> Option<&mut ConfigArgs> {
+        match &mut self.command {
+            None => Some(&mut self.convert.settings),
+            Some(Command::Convert(args)) => Some(&mut args.settings),
+            Some(Command::Merge(args)) => Some(&mut args.settings),
+            Some(Command::Summary(args)) => Some(&mut args.settings),
+            Some(Command::Diff(args)) => Some(&mut args.settings),
+            Some(Command::Compare(args)) => Some(&mut args.settings),
+            Some(Command::Validate(args)) => Some(&mut args.settings),
+            Some(Command::Split(_)) => None,
+        }
+    }
+}
+
+/// Input files of the subcommands reading LCOV
+#[derive(clap::Args, Debug)]
+struct InputArgs {
+    /// LCOV input files, directories or glob patterns like `target/**/lcov.info`, use single
+    /// dash '-' argument to read from standard input
     #[clap()]
     files: Vec<PathBuf>,
+    /// File listing further input files, one per line or NUL-separated like `find -print0`
+    /// writes them, `-` reads the list from standard input
+    #[clap(long, value_name = "FILE")]
+    files_from: Option<PathBuf>,
+    /// Search input directories recursively
+    #[clap(short, long)]
+    recursive: bool,
+    /// File name glob of the files read from input directories
+    #[clap(long, value_name = "GLOB", default_value = "*.info")]
+    name: String,
+    /// Print the input files found in directories and by glob patterns
+    #[clap(short, long)]
+    verbose: bool,
+}
+
+impl InputArgs {
+    /// The input files with directories and glob patterns expanded, followed by those of
+    /// `--files-from`, standard input is read at most once
+    fn files(&self) -> anyhow::Result<Vec<PathBuf>> {
+        let search = lcov2xml::InputSearch {
+            recursive: self.recursive,
+            name: self.name.clone(),
+        };
+        let mut files = lcov2xml::expand_inputs(&self.files, &search)?;
+        let stdin = Path::new("-");
+        if let Some(list) = &self.files_from {
+            let content = if list == stdin {
+                anyhow::ensure!(
+                    !files.iter().any(|f| f == stdin),
+                    "standard input cannot be read as input file and by --files-from"
+                );
+                let mut content = Vec::new();
+                std::io::stdin().lock().read_to_end(&mut content)?;
+                content
+            } else {
+                std::fs::read(list)?
+            };
+            files.extend(lcov2xml::read_file_list(&content)?);
+        }
+        anyhow::ensure!(
+            files.iter().filter(|f| *f == stdin).count() <= 1,
+            "standard input can be read only once, '-' is given several times"
+        );
+        if self.verbose {
+            eprintln!("input files ({}):", files.len());
+            for file in &files {
+                eprintln!("  {}", file.display());
+            }
+        }
+        Ok(files)
+    }
+}
+
+/// How the source files of LCOV input are filtered, named and grouped into packages
+#[derive(clap::Args, Debug)]
+struct ParseArgs {
     /// Directory where source files are located
     #[clap(short, long, default_value = ".")]
     base_dir: PathBuf,
-    /// Path to store cobertura XML file
-    #[clap(short, long, default_value = "coverage.xml")]
-    output: PathBuf,
+    /// Further source root, repeatable, files are made relative to the longest root containing
+    /// them and all roots are written as `<source>`
+    #[clap(long = "source-root", value_name = "DIR")]
+    source_roots: Vec<String>,
+    /// Number of threads parsing multiple input files, all cores by default
+    #[cfg(feature = "parallel")]
+    #[clap(short, long)]
+    jobs: Option<usize>,
     /// Comma-separated list of regexes of packages to exclude
     #[clap(short, long, default_value = "")]
     excludes: String,
+    /// Only keep source files matching one of these gitignore-style globs, e.g. `src/**`,
+    /// repeatable or comma-separated
+    #[clap(long, value_delimiter = ',')]
+    include: Vec<String>,
+    /// Drop source files matching one of these gitignore-style globs, e.g. `**/generated/`,
+    /// repeatable or comma-separated
+    #[clap(long, value_delimiter = ',')]
+    exclude_file: Vec<String>,
+    /// Rewrite source paths before naming, `FROM=TO` replaces a leading path, `regex:FROM=TO`
+    /// the first regex match, repeatable, the first matching rule wins
+    #[clap(long = "path-map", value_name = "FROM=TO")]
+    path_maps: Vec<lcov2xml::PathMap>,
+    /// Drop source files whose (mapped) path lies outside the base directory, e.g. system headers
+    #[clap(long)]
+    drop_outside_base_dir: bool,
+    /// Resolve symlinks of the base directory and source files, by default paths are only
+    /// normalized lexically
+    #[clap(long)]
+    canonicalize: bool,
+    /// Separator in the `filename` attribute: `native`, `unix` (`/`) or `windows` (`\`)
+    #[clap(long, default_value = "native")]
+    path_style: lcov2xml::PathStyle,
+    /// Package naming: `full` (every directory), `depth:N` (first N directories) or
+    /// `regex:PATTERN` (capture groups joined with `.`)
+    #[clap(long, default_value = "full")]
+    package_naming: lcov2xml::PackageNaming,
+    /// TOML file mapping path prefixes to package names in a `[packages]` table, longest prefix
+    /// wins, other files are named after their full directory
+    #[clap(long, conflicts_with = "package_naming")]
+    package_map: Option<PathBuf>,
+}
+
+/// Config file of default arguments, see the `config` module
+#[derive(clap::Args, Debug)]
+struct ConfigArgs {
+    /// Config file with default arguments, keys are the long option names. By default
+    /// `lcov2xml.toml` or the `[package.metadata.lcov2xml]` table of a `Cargo.toml` in the base
+    /// directory or its closest parent
+    #[clap(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+    /// Print the effective settings in the format of the config file and exit
+    #[clap(long)]
+    print_config: bool,
+    /// tables of the config file given instead of the files of `--package-map`,
+    /// `--rewrite-rules` and `--thresholds`
+    #[clap(skip)]
+    inline: config::InlineTables,
+    /// config file the settings were read from
+    #[clap(skip)]
+    config_file: Option<PathBuf>,
+}
+
+/// Output of the subcommands printing their result
+#[derive(clap::Args, Debug)]
+struct OutputArgs {
+    /// File to write to instead of standard output, `-` is standard output
+    #[clap(short, long)]
+    output: Option<PathBuf>,
+}
+
+impl OutputArgs {
+    /// the output file or standard output, has to be flushed
+    fn writer(&self) -> anyhow::Result<Box<dyn Write>> {
+        Ok(match &self.output {
+            Some(path) if path != Path::new("-") => {
+                Box::new(std::io::BufWriter::new(std::fs::File::create(path)?))
+            }
+            _ => Box::new(std::io::stdout().lock()),
+        })
+    }
+}
+
+/// How method names are demangled, rewritten and grouped
+#[derive(clap::Args, Debug)]
+#[allow(clippy::struct_excessive_bools, reason = "command line flags")]
+struct DemangleArgs {
     /// Demangle function names
     #[clap(short, long)]
     demangle: bool,
-    /// Path to demangler tool, e.g. `c++filt` for C++, `$rust` = internal rustc demangler
+    /// Path to demangler tool, e.g. `c++filt` for C++, `$rust` = internal rustc demangler,
+    /// `$cpp` = internal Itanium C++ demangler (cargo feature `cpp-demangle`), `$swift` =
+    /// `swift-demangle` of the Swift toolchain, `$d` = internal D demangler, `auto` = Rust, C++,
+    /// Swift or D per symbol, C++ with `$cpp` if available, `c++filt` otherwise, `$null` = keep
+    /// names
     #[clap(long, default_value = "$rust")]
-    demangler: String,
+    demangler: lcov2xml::DemanglerKind,
+    /// Method `name` and `signature` attributes: `full` (whole demangled name as name), `short`
+    /// (name without return type and parameters) or `split` (short name, parameters and
+    /// qualifiers as signature)
+    #[clap(long, default_value = "full", value_name = "STYLE")]
+    method_names: lcov2xml::MethodNames,
+    /// Merge functions into one method: `none`, `line` (functions starting at the same line) or
+    /// `name` (equal demangled names without Rust hashes and `[clone]` suffixes), e.g. instances
+    /// of generics and templates. Hits are summed
+    #[clap(long, default_value = "none", value_name = "GROUPING")]
+    group_methods: lcov2xml::MethodGrouping,
+    /// Rewrite method names after demangling, `PATTERN=REPLACEMENT` replaces every match of the
+    /// regex, repeatable, applied in order after the presets
+    #[clap(long = "rewrite", value_name = "PATTERN=REPLACEMENT")]
+    rewrites: Vec<lcov2xml::RewriteRule>,
+    /// Built-in method name rewrites: `rust` (drop hashes and closures, collapse generics) or
+    /// `cpp` (strip inline namespaces of libc++ and libstdc++, shorten `std::string`, drop default
+    /// allocators), repeatable or comma-separated
+    #[clap(long = "rewrite-preset", value_delimiter = ',', value_name = "PRESET")]
+    rewrite_presets: Vec<lcov2xml::RewritePreset>,
+    /// TOML file with method name rewrites, a `presets` list and `[[rule]]` tables with `pattern`
+    /// and `replacement`, applied before `--rewrite-preset` and `--rewrite`
+    #[clap(long, value_name = "FILE")]
+    rewrite_rules: Option<PathBuf>,
+    /// Seconds an external demangler tool has to answer a batch of names before they are kept
+    /// mangled
+    #[clap(long, default_value = "10", value_name = "SECONDS")]
+    demangler_timeout: f64,
+    /// Omit the function parameters when demangling with `$cpp` or `auto`
+    #[cfg(feature = "cpp-demangle")]
+    #[clap(long)]
+    demangle_no_params: bool,
+    /// Omit the return types of template functions when demangling with `$cpp` or `auto`
+    #[cfg(feature = "cpp-demangle")]
+    #[clap(long)]
+    demangle_no_return_type: bool,
+    /// Demangle Swift names with `swift-demangle --simplified` when using `$swift` or `auto`,
+    /// e.g. `Foo.bar(_:)` instead of `main.Foo.bar(Swift.Int) -> ()`
+    #[clap(long)]
+    demangle_simplified: bool,
+}
+
+/// Sorting and size of summary tables
+#[derive(clap::Args, Debug)]
+struct SummaryTableArgs {
+    /// Sort summary packages by `name`, `lines`, `branches` or `functions`, rates worst first
+    #[clap(long = "summary-sort", default_value = "name")]
+    sort: lcov2xml::SummarySort,
+    /// Additionally list the N files with the lowest line rate in the summary
+    #[clap(long = "summary-worst", value_name = "N")]
+    worst: Option<usize>,
+    /// Merge summary packages nested deeper than this many name components into their parent
+    #[clap(long = "summary-depth", value_name = "N")]
+    depth: Option<usize>,
+}
+
+impl SummaryTableArgs {
+    fn options(&self, format: lcov2xml::SummaryFormat) -> lcov2xml::SummaryOptions {
+        lcov2xml::SummaryOptions {
+            format,
+            sort: self.sort,
+            worst_files: self.worst,
+            depth: self.depth,
+        }
+    }
+}
+
+/// The total coverage line for the GitLab `coverage:` regex
+#[derive(clap::Args, Debug)]
+struct TotalArgs {
+    /// Print `Total line coverage: 83.21% (1234/1483), branch: ..., function: ...` for the
+    /// GitLab `coverage:` regex `^Total \w+ coverage: (\d+\.\d+)%`
+    #[clap(long)]
+    total_line: bool,
+    /// Metric printed first in the total coverage line: `lines`, `branches` or `functions`
+    #[clap(long, default_value = "lines")]
+    total_first: lcov2xml::Metric,
+}
+
+/// Minimum total, package and file coverage
+#[derive(clap::Args, Debug)]
+struct ThresholdArgs {
+    /// Fail with exit code 3 if the total line coverage in percent is below this value
+    #[clap(long)]
+    fail_under_lines: Option<f64>,
+    /// Fail with exit code 3 if the total branch coverage in percent is below this value
+    #[clap(long)]
+    fail_under_branches: Option<f64>,
+    /// Fail with exit code 3 if the total function coverage in percent is below this value
+    #[clap(long)]
+    fail_under_functions: Option<f64>,
+    /// TOML file with per-package and per-file minimum coverage, checked like `--fail-under-*`
+    #[clap(long)]
+    thresholds: Option<PathBuf>,
+}
+
+/// Arguments of the `convert` subcommand and of `lcov2xml` without a subcommand
+#[derive(clap::Args, Debug)]
+struct ConvertArgs {
+    #[clap(flatten)]
+    input: InputArgs,
+    #[clap(flatten)]
+    parse: ParseArgs,
+    /// Path to store cobertura XML file, `-` writes it to standard output and the summary, total
+    /// line and diff coverage to standard error
+    #[clap(short, long, default_value = "coverage.xml")]
+    output: PathBuf,
+    #[clap(flatten)]
+    demangle: DemangleArgs,
+    /// Write every source file as soon as its record ends, memory scales with the largest source
+    /// file instead of the whole report. Needs a single input with records grouped by package and
+    /// source file
+    #[clap(long, conflicts_with_all = [
+        "summary", "fail_under_lines", "fail_under_branches", "fail_under_functions",
+        "thresholds", "diff",
+    ])]
+    streaming: bool,
     /// Splits XML file into 9.5 megabytes big chunks for GitLab, attention keeps original file intact
     #[clap(long)]
     split_xml: bool,
+    /// Print a per-package coverage summary table, format `text` or `markdown`
+    #[clap(long)]
+    summary: Option<lcov2xml::SummaryFormat>,
+    #[clap(flatten)]
+    table: SummaryTableArgs,
+    /// Write the summary next to the XML file (`.txt` or `.md`) instead of standard output
+    #[clap(long)]
+    summary_to_file: bool,
+    #[clap(flatten)]
+    total: TotalArgs,
+    #[clap(flatten)]
+    thresholds: ThresholdArgs,
+    /// Unified diff, e.g. from `git diff -U0 main...HEAD`, prints coverage of the changed lines
+    #[clap(long)]
+    diff: Option<PathBuf>,
+    /// Fail with exit code 3 if the coverage of the changed lines in percent is below this value
+    #[clap(long, requires = "diff")]
+    fail_under_diff: Option<f64>,
+    /// Directory to write a static HTML report to
+    #[cfg(feature = "html")]
+    #[clap(long, conflicts_with = "streaming")]
+    html: Option<PathBuf>,
+    #[clap(flatten)]
+    settings: ConfigArgs,
 }
 
-fn now() -> anyhow::Result<u64> {
-    match SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
-        Ok(n) => Ok(n.as_secs()),
-        Err(_) => anyhow::bail!("SystemTime before UNIX EPOCH!"),
+/// Arguments of the `split` subcommand
+#[derive(clap::Args, Debug)]
+struct SplitArgs {
+    /// cobertura XML input file
+    filename: PathBuf,
+}
+
+/// Arguments of the `merge` subcommand
+#[derive(clap::Args, Debug)]
+struct MergeArgs {
+    #[clap(flatten)]
+    input: InputArgs,
+    #[clap(flatten)]
+    output: OutputArgs,
+    #[clap(flatten)]
+    settings: ConfigArgs,
+}
+
+/// Arguments of the `summary` subcommand
+#[derive(clap::Args, Debug)]
+struct SummaryArgs {
+    #[clap(flatten)]
+    input: InputArgs,
+    #[clap(flatten)]
+    output: OutputArgs,
+    #[clap(flatten)]
+    parse: ParseArgs,
+    /// Table format: `text` or `markdown`
+    #[clap(long, default_value = "text")]
+    format: lcov2xml::SummaryFormat,
+    #[clap(flatten)]
+    table: SummaryTableArgs,
+    #[clap(flatten)]
+    total: TotalArgs,
+    #[clap(flatten)]
+    thresholds: ThresholdArgs,
+    #[clap(flatten)]
+    settings: ConfigArgs,
+}
+
+/// Arguments of the `diff` subcommand
+#[derive(clap::Args, Debug)]
+struct DiffArgs {
+    #[clap(flatten)]
+    input: InputArgs,
+    #[clap(flatten)]
+    output: OutputArgs,
+    #[clap(flatten)]
+    parse: ParseArgs,
+    /// Unified diff, e.g. from `git diff -U0 main...HEAD`
+    #[clap(long)]
+    diff: PathBuf,
+    /// Fail with exit code 3 if the coverage of the changed lines in percent is below this value
+    #[clap(long)]
+    fail_under_diff: Option<f64>,
+    #[clap(flatten)]
+    settings: ConfigArgs,
+}
+
+/// Arguments of the `compare` subcommand
+#[derive(clap::Args, Debug)]
+struct CompareArgs {
+    /// Old report, e.g. of the target branch, files ending in `.xml` are read as cobertura XML,
+    /// `-` reads LCOV from standard input
+    before: PathBuf,
+    /// New report, e.g. of the merge request branch
+    after: PathBuf,
+    #[clap(flatten)]
+    output: OutputArgs,
+    #[clap(flatten)]
+    parse: ParseArgs,
+    /// Output format: `text`, `markdown` or `json`
+    #[clap(long, default_value = "text")]
+    format: lcov2xml::CompareFormat,
+    /// Fail with exit code 3 if the total line or branch rate dropped by more than this many
+    /// percentage points
+    #[clap(long)]
+    max_regression: Option<f64>,
+    #[clap(flatten)]
+    settings: ConfigArgs,
+}
+
+/// Arguments of the `validate` subcommand
+#[derive(clap::Args, Debug)]
+struct ValidateArgs {
+    #[clap(flatten)]
+    input: InputArgs,
+    #[clap(flatten)]
+    parse: ParseArgs,
+    #[clap(flatten)]
+    settings: ConfigArgs,
+}
+
+/// Reads a report of `compare`, files ending in `.xml` as cobertura XML, all others as LCOV
+/// named like `convert` names them
+fn read_report(
+    filename: &Path,
+    parse: &ParseArgs,
+    settings: &ConfigArgs,
+) -> anyhow::Result<lcov2xml::CoverageData> {
+    let excludes = parse.excludes();
+    if filename.extension().is_some_and(|ext| ext == "xml") {
+        return lcov2xml::parse_cobertura_file(filename, &excludes);
+    }
+    let result = parse.parse_file(filename, &excludes, &parse.options(settings)?)?;
+    parse.report_filtered(result.filtered_records());
+    Ok(result)
+}
+
+fn compare(args: &CompareArgs) -> anyhow::Result<ExitCode> {
+    anyhow::ensure!(
+        args.before != Path::new("-") || args.after != Path::new("-"),
+        "standard input can be read only once, '-' is given twice"
+    );
+    let before = read_report(&args.before, &args.parse, &args.settings)?;
+    let after = read_report(&args.after, &args.parse, &args.settings)?;
+    let comparison = lcov2xml::compare(&before, &after);
+    let mut output = args.output.writer()?;
+    write!(output, "{}", comparison.render(args.format))?;
+    output.flush()?;
+    if let Some(budget) = args.max_regression {
+        let regression = comparison.regression();
+        if regression > budget {
+            eprintln!(
+                "Coverage dropped by {regression:.2} percentage points, the budget is {budget:.2}"
+            );
+            return Ok(ExitCode::from(EXIT_BELOW_THRESHOLD));
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Exit code when coverage is below a `--fail-under-*` or `--thresholds` value, clap exits
+/// with 2 on usage errors
+const EXIT_BELOW_THRESHOLD: u8 = 3;
+
+/// Exit codes listed at the end of `--help`
+const EXIT_CODES: &str =
+    "Exit codes: 0 success, 1 error or invalid input, 2 usage error, 3 coverage \
+     below a --fail-under-*, --thresholds or --max-regression limit";
+
+/// Per-package and per-file thresholds read from `--thresholds`
+#[derive(Debug, Default, Clone, serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
+struct ThresholdsFile {
+    #[serde(default)]
+    package: Vec<ThresholdEntry>,
+    #[serde(default)]
+    file: Vec<ThresholdEntry>,
+}
+
+/// Thresholds for packages or files matching the regex `pattern`
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
+struct ThresholdEntry {
+    pattern: String,
+    lines: Option<f64>,
+    branches: Option<f64>,
+    functions: Option<f64>,
+}
+
+impl ThresholdEntry {
+    fn into_rule(self, scope: lcov2xml::ThresholdScope) -> lcov2xml::ThresholdRule {
+        lcov2xml::ThresholdRule {
+            scope,
+            pattern: self.pattern,
+            thresholds: lcov2xml::Thresholds {
+                lines: self.lines,
+                branches: self.branches,
+                functions: self.functions,
+            },
+        }
     }
 }
 
-fn main() -> anyhow::Result<()> {
-    let args = Args::parse();
-    let excludes: Vec<&str> = args.excludes.split(',').filter(|v| !v.is_empty()).collect();
-    let result = if args.files.len() > 1 {
-        // Merge into memory and pass to line-parser
-        let mut report = lcov::Report::new();
+impl ThresholdArgs {
+    /// Checks the `--fail-under-*` options and `--thresholds`, prints failures and returns
+    /// `false` if any failed
+    fn check(
+        &self,
+        settings: &ConfigArgs,
+        result: &lcov2xml::CoverageData,
+    ) -> anyhow::Result<bool> {
+        let total = lcov2xml::Thresholds {
+            lines: self.fail_under_lines,
+            branches: self.fail_under_branches,
+            functions: self.fail_under_functions,
+        };
+        let mut rules = vec![];
+        let file = match &self.thresholds {
+            Some(path) => Some(toml::from_str(&std::fs::read_to_string(path)?)?),
+            None => settings.inline.thresholds.clone(),
+        };
+        if let Some(file) = file {
+            rules.extend(
+                file.package
+                    .into_iter()
+                    .map(|e| e.into_rule(lcov2xml::ThresholdScope::Package)),
+            );
+            rules.extend(
+                file.file
+                    .into_iter()
+                    .map(|e| e.into_rule(lcov2xml::ThresholdScope::File)),
+            );
+        }
+        let failures = lcov2xml::check_thresholds(result, &total, &rules)?;
+        if !failures.is_empty() {
+            eprintln!("Coverage below threshold:");
+            for failure in &failures {
+                eprintln!("  {failure}");
+            }
+        }
+        Ok(failures.is_empty())
+    }
+}
+
+/// Writes the coverage of the lines changed in the unified diff `path`, returns `false` if it is
+/// below `fail_under`
+fn check_diff(
+    result: &lcov2xml::CoverageData,
+    path: &Path,
+    fail_under: Option<f64>,
+    output: &mut dyn Write,
+) -> anyhow::Result<bool> {
+    let changed = lcov2xml::parse_unified_diff(&std::fs::read_to_string(path)?)?;
+    let diff_coverage = lcov2xml::diff_coverage(result, &changed);
+    write!(output, "{}", diff_coverage.render())?;
+    if let Some(required) = fail_under {
+        let actual = diff_coverage.rate() * 100.;
+        if diff_coverage.total() > 0 && actual < required {
+            eprintln!("Diff coverage {actual:.2}% is below {required:.2}%");
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+impl ParseArgs {
+    fn excludes(&self) -> Vec<&str> {
+        self.excludes.split(',').filter(|v| !v.is_empty()).collect()
+    }
+
+    fn options(&self, settings: &ConfigArgs) -> anyhow::Result<lcov2xml::ParseOptions> {
+        Ok(lcov2xml::ParseOptions {
+            include: self.include.clone(),
+            exclude_file: self.exclude_file.clone(),
+            path_maps: self.path_maps.clone(),
+            drop_outside_base_dir: self.drop_outside_base_dir,
+            canonicalize_paths: self.canonicalize,
+            path_style: self.path_style,
+            package_naming: self.package_naming(settings)?,
+            source_roots: self.source_roots.clone(),
+        })
+    }
+
+    /// Package naming of `--package-map`, a `package-map` table of the config file or
+    /// `--package-naming`
+    fn package_naming(&self, settings: &ConfigArgs) -> anyhow::Result<lcov2xml::PackageNaming> {
+        if let Some(path) = &self.package_map {
+            let file: PackageMapFile = toml::from_str(&std::fs::read_to_string(path)?)?;
+            return Ok(file.into_naming());
+        }
+        Ok(match &settings.inline.package_map {
+            Some(file) => file.clone().into_naming(),
+            None => self.package_naming.clone(),
+        })
+    }
+
+    /// Parses a single input file, `-` reads standard input
+    fn parse_file(
+        &self,
+        filename: &Path,
+        excludes: &[&str],
+        options: &lcov2xml::ParseOptions,
+    ) -> anyhow::Result<lcov2xml::CoverageData> {
+        if filename == Path::new("-") {
+            let mut input = Vec::new();
+            std::io::stdin().lock().read_to_end(&mut input)?;
+            lcov2xml::parse_lines_with(input.lines(), self.base_dir.as_path(), excludes, options)
+        } else {
+            lcov2xml::parse_file_with(filename, self.base_dir.as_path(), excludes, options)
+        }
+    }
+
+    /// Parses and merges all input files and reports the filtered records
+    fn read(
+        &self,
+        input: &InputArgs,
+        settings: &ConfigArgs,
+    ) -> anyhow::Result<lcov2xml::CoverageData> {
+        let excludes = self.excludes();
+        let options = self.options(settings)?;
+        let result = match input.files()?.as_slice() {
+            [] => anyhow::bail!("no filename given"),
+            [filename] => self.parse_file(filename, &excludes, &options)?,
+            files => self.parse_multiple(files, &excludes, &options)?,
+        };
+        self.report_filtered(result.filtered_records());
+        Ok(result)
+    }
+
+    /// Parses every input file on a thread pool and merges the results
+    #[cfg(feature = "parallel")]
+    fn parse_multiple(
+        &self,
+        files: &[PathBuf],
+        excludes: &[&str],
+        options: &lcov2xml::ParseOptions,
+    ) -> anyhow::Result<lcov2xml::CoverageData> {
+        let (stdin, files): (Vec<&PathBuf>, Vec<&PathBuf>) =
+            files.iter().partition(|f| *f == Path::new("-"));
+        let mut result =
+            lcov2xml::parse_files_parallel(&files, &self.base_dir, excludes, options, self.jobs)?;
+        if let Some(stdin) = stdin.first() {
+            result.merge(self.parse_file(stdin, excludes, options)?);
+        }
+        Ok(result)
+    }
 
+    /// Merges all input files in memory and passes them to the line parser
+    #[cfg(not(feature = "parallel"))]
+    fn parse_multiple(
+        &self,
+        files: &[PathBuf],
+        excludes: &[&str],
+        options: &lcov2xml::ParseOptions,
+    ) -> anyhow::Result<lcov2xml::CoverageData> {
         let mut totalsize = 0;
-        for filename in args.files {
+        for filename in files.iter().filter(|f| *f != Path::new("-")) {
             totalsize += filename.metadata()?.len();
-            report.merge(lcov::Report::from_file(filename)?)?;
         }
-
         let mut merged = String::with_capacity(usize::try_from(totalsize)?);
-        for record in report.into_records() {
+        for record in merge_reports(files)?.into_records() {
             merged.push_str(&record.to_string());
             merged.push('\n');
         }
 
-        lcov2xml::parse_lines(
+        lcov2xml::parse_lines_with(
             merged.as_bytes().lines(),
-            args.base_dir.as_path(),
-            &excludes,
-        )?
-    } else if args.files.first() == Some(&PathBuf::from("-")) {
-        let mut input = Vec::new();
-        let stdin = std::io::stdin();
-        let mut handle = stdin.lock();
-        handle.read_to_end(&mut input)?;
-        lcov2xml::parse_lines(input.lines(), args.base_dir.as_path(), &excludes)?
+            self.base_dir.as_path(),
+            excludes,
+            options,
+        )
+    }
+
+    /// Prints the number of records dropped by the file filters
+    fn report_filtered(&self, filtered_records: usize) {
+        if !self.include.is_empty() || !self.exclude_file.is_empty() || self.drop_outside_base_dir {
+            eprintln!(
+                "{filtered_records} records filtered by --include/--exclude-file/--drop-outside-base-dir"
+            );
+        }
+    }
+}
+
+/// Merges LCOV files with the `lcov` crate, records of the same source file are combined, `-`
+/// reads standard input
+fn merge_reports(files: &[PathBuf]) -> anyhow::Result<lcov::Report> {
+    let mut report = lcov::Report::new();
+    for filename in files {
+        if filename == Path::new("-") {
+            let reader = lcov::Reader::new(std::io::stdin().lock());
+            report.merge(lcov::Report::from_reader(reader)?)?;
+        } else {
+            report.merge(lcov::Report::from_file(filename)?)?;
+        }
+    }
+    Ok(report)
+}
+
+/// Method name rewrites read from `--rewrite-rules`
+#[derive(Debug, Default, Clone, serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
+struct RewriteFile {
+    #[serde(default)]
+    presets: Vec<String>,
+    #[serde(default)]
+    rule: Vec<RewriteEntry>,
+}
+
+/// Replaces every match of the regex `pattern` with `replacement`
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
+struct RewriteEntry {
+    pattern: String,
+    #[serde(default)]
+    replacement: String,
+}
+
+impl DemangleArgs {
+    /// Rewrite rules of `--rewrite-rules`, `--rewrite-preset` and `--rewrite`: presets first,
+    /// then the rules, each of the file before those of the command line
+    fn rewrite_rules(&self, settings: &ConfigArgs) -> anyhow::Result<Vec<lcov2xml::RewriteRule>> {
+        let file: RewriteFile = match &self.rewrite_rules {
+            Some(path) => toml::from_str(&std::fs::read_to_string(path)?)?,
+            None => settings.inline.rewrite_rules.clone().unwrap_or_default(),
+        };
+        let mut presets = file
+            .presets
+            .iter()
+            .map(|preset| preset.parse())
+            .collect::<anyhow::Result<Vec<lcov2xml::RewritePreset>>>()?;
+        presets.extend(&self.rewrite_presets);
+        let mut rules: Vec<lcov2xml::RewriteRule> = presets
+            .into_iter()
+            .flat_map(lcov2xml::RewritePreset::rules)
+            .collect();
+        for entry in file.rule {
+            rules.push(lcov2xml::RewriteRule::new(
+                &entry.pattern,
+                &entry.replacement,
+            )?);
+        }
+        rules.extend(self.rewrites.iter().cloned());
+        Ok(rules)
+    }
+
+    fn options(&self) -> anyhow::Result<lcov2xml::DemanglerOptions> {
+        Ok(lcov2xml::DemanglerOptions {
+            timeout: std::time::Duration::try_from_secs_f64(self.demangler_timeout)?,
+            #[cfg(feature = "cpp-demangle")]
+            no_params: self.demangle_no_params,
+            #[cfg(feature = "cpp-demangle")]
+            no_return_type: self.demangle_no_return_type,
+            swift_simplified: self.demangle_simplified,
+        })
+    }
+}
+
+/// Path prefix to package name mapping read from `--package-map`
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
+struct PackageMapFile {
+    packages: std::collections::BTreeMap<String, String>,
+}
+
+impl PackageMapFile {
+    fn into_naming(self) -> lcov2xml::PackageNaming {
+        lcov2xml::PackageNaming::Mapping(self.packages.into_iter().collect())
+    }
+}
+
+fn now() -> anyhow::Result<u64> {
+    match SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(n) => Ok(n.as_secs()),
+        Err(_) => anyhow::bail!("SystemTime before UNIX EPOCH!"),
+    }
+}
+
+impl ConvertArgs {
+    /// `-o -` writes the XML to standard output
+    fn xml_to_stdout(&self) -> bool {
+        self.output == Path::new("-")
+    }
+
+    /// Where the summary, total line and diff coverage are printed, standard error if the XML
+    /// goes to standard output
+    fn report_writer(&self) -> Box<dyn Write> {
+        if self.xml_to_stdout() {
+            Box::new(std::io::stderr())
+        } else {
+            Box::new(std::io::stdout())
+        }
+    }
+}
+
+/// Options of the written XML
+fn xml_options(args: &ConvertArgs) -> lcov2xml::XmlOptions {
+    lcov2xml::XmlOptions {
+        method_names: args.demangle.method_names,
+        method_grouping: args.demangle.group_methods,
+    }
+}
+
+/// Writes all requested reports, `new_demangler` is called once per report
+fn write_reports<D, F>(
+    args: &ConvertArgs,
+    result: &lcov2xml::CoverageData,
+    new_demangler: F,
+) -> anyhow::Result<()>
+where
+    D: lcov2xml::Demangler,
+    F: Fn() -> std::io::Result<D>,
+{
+    if args.xml_to_stdout() {
+        let mut stdout = lcov2xml::coverage_to_writer_with(
+            std::io::BufWriter::new(std::io::stdout().lock()),
+            result,
+            now()?,
+            new_demangler()?,
+            &xml_options(args),
+        )?;
+        stdout.flush()?;
     } else {
-        let filename = args
-            .files
-            .first()
-            .ok_or_else(|| anyhow::anyhow!("no filename given"))?;
-        lcov2xml::parse_file(filename.as_path(), args.base_dir.as_path(), &excludes)?
+        lcov2xml::coverage_to_file_with(
+            &args.output,
+            result,
+            now()?,
+            new_demangler()?,
+            &xml_options(args),
+        )?;
+    }
+    #[cfg(feature = "html")]
+    if let Some(html_dir) = &args.html {
+        lcov2xml::coverage_to_html(html_dir, result, new_demangler()?)?;
+    }
+    Ok(())
+}
+
+fn main() -> anyhow::Result<ExitCode> {
+    let (mut cli, command, matches) = config::parse_args()?;
+    if let Some(settings) = cli.settings_mut() {
+        if settings.print_config {
+            print!("{}", config::render(settings, &command, &matches)?);
+            return Ok(ExitCode::SUCCESS);
+        }
+    }
+    match &cli.command {
+        None => convert(&cli.convert),
+        Some(Command::Convert(args)) => convert(args),
+        Some(Command::Split(args)) => {
+            lcov2xml::corbertura_xml_split(&args.filename)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Some(Command::Merge(args)) => merge(args),
+        Some(Command::Summary(args)) => summary(args),
+        Some(Command::Diff(args)) => diff(args),
+        Some(Command::Compare(args)) => compare(args),
+        Some(Command::Validate(args)) => validate(args),
+    }
+}
+
+/// Converts the input files with the demangler of the arguments
+fn convert(args: &ConvertArgs) -> anyhow::Result<ExitCode> {
+    let demangler = if args.demangle.demangle {
+        args.demangle.demangler.clone()
+    } else {
+        lcov2xml::DemanglerKind::Null
     };
+    let demangler_options = args.demangle.options()?;
+    let rules = args.demangle.rewrite_rules(&args.settings)?;
+    convert_with(args, || {
+        Ok(WarningDemangler(lcov2xml::RewritingDemangler::new(
+            demangler.build(&demangler_options)?,
+            rules.clone(),
+        )))
+    })
+}
 
-    // This is done repetitively to avoid dynamic dispatching. When a fourth demangler is added
-    // implement enum dispatching ;)
-    if args.demangle {
-        if args.demangler == "$rust" {
-            let demangler = lcov2xml::RustDemangler::new();
-            lcov2xml::coverage_to_file(&args.output, &result, now()?, demangler)?;
-        } else {
-            let demangler = lcov2xml::CppDemangler::new(&args.demangler)?;
-            lcov2xml::coverage_to_file(&args.output, &result, now()?, demangler)?;
+/// Prints the diagnostics of the wrapped demangler as warnings once it is stopped
+struct WarningDemangler<D>(D);
+
+impl<D: lcov2xml::Demangler> lcov2xml::Demangler for WarningDemangler<D> {
+    fn demangle<'a>(&mut self, ident: &'a str) -> std::io::Result<std::borrow::Cow<'a, str>> {
+        self.0.demangle(ident)
+    }
+
+    fn prepare(&mut self, idents: &[&str]) -> std::io::Result<()> {
+        self.0.prepare(idents)
+    }
+
+    fn stop(&mut self) -> std::io::Result<()> {
+        self.0.stop()?;
+        for diagnostic in self.0.take_diagnostics() {
+            eprintln!("warning: {diagnostic}");
         }
+        Ok(())
+    }
+}
+
+/// Converts the input files and writes the requested reports and checks, `new_demangler` is
+/// called once per report
+fn convert_with<D, F>(args: &ConvertArgs, new_demangler: F) -> anyhow::Result<ExitCode>
+where
+    D: lcov2xml::Demangler,
+    F: Fn() -> std::io::Result<D> + Sync,
+{
+    if args.xml_to_stdout() {
+        anyhow::ensure!(
+            !args.split_xml && !args.summary_to_file,
+            "--split-xml and --summary-to-file need an XML file, not standard output"
+        );
+    }
+    if args.streaming {
+        // bounded, so memory doesn't grow with the number of unique names
+        let demangler =
+            lcov2xml::CachingDemangler::bounded(new_demangler()?, STREAMING_CACHED_NAMES);
+        return convert_streaming(args, demangler);
+    }
+    let mut result = args.parse.read(&args.input, &args.settings)?;
+
+    // every unique name is demangled once, up front on all cores with the `parallel` feature,
+    // otherwise by the caching demangler of each report
+    #[cfg(feature = "parallel")]
+    let names = if args.demangle.demangle {
+        lcov2xml::demangle_parallel(&result.method_names(), &new_demangler, args.parse.jobs)?
     } else {
-        let demangler = lcov2xml::NullDemangler::new();
-        lcov2xml::coverage_to_file(&args.output, &result, now()?, demangler)?;
+        HashMap::new()
+    };
+    #[cfg(not(feature = "parallel"))]
+    let names = HashMap::new();
+    let grouping = args.demangle.group_methods;
+    if grouping != lcov2xml::MethodGrouping::None {
+        let mut demangler = lcov2xml::CachingDemangler::with_names(new_demangler()?, names.clone());
+        result.group_methods(grouping, &mut demangler)?;
+        lcov2xml::Demangler::stop(&mut demangler)?;
+    }
+    write_reports(args, &result, || {
+        Ok(lcov2xml::CachingDemangler::with_names(
+            new_demangler()?,
+            names.clone(),
+        ))
+    })?;
+
+    if let Some(format) = args.summary {
+        let table = lcov2xml::render_summary(&result, &args.table.options(format));
+        if args.summary_to_file {
+            std::fs::write(args.output.with_extension(format.extension()), table)?;
+        } else {
+            write!(args.report_writer(), "{table}")?;
+        }
+    }
+
+    if args.total.total_line {
+        writeln!(
+            args.report_writer(),
+            "{}",
+            lcov2xml::total_coverage_line(&result, args.total.total_first)
+        )?;
     }
 
     if args.split_xml {
         lcov2xml::corbertura_xml_split(&args.output)?;
     }
-    Ok(())
+
+    let mut passed = args.thresholds.check(&args.settings, &result)?;
+    if let Some(path) = &args.diff {
+        passed &= check_diff(
+            &result,
+            path,
+            args.fail_under_diff,
+            &mut args.report_writer(),
+        )?;
+    }
+    if !passed {
+        return Ok(ExitCode::from(EXIT_BELOW_THRESHOLD));
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Demangled names kept by `--streaming`, names shared by many files like those of generics or
+/// the standard library are usually among them
+const STREAMING_CACHED_NAMES: usize = 4096;
+
+/// Converts a single input with [`lcov2xml::stream_to_file`], only the total line and the
+/// splitting work without the whole report in memory
+fn convert_streaming<D: lcov2xml::Demangler>(
+    args: &ConvertArgs,
+    demangler: D,
+) -> anyhow::Result<ExitCode> {
+    anyhow::ensure!(
+        !args.xml_to_stdout(),
+        "--streaming seeks back into the XML file and cannot write to standard output"
+    );
+    let files = args.input.files()?;
+    let [file] = files.as_slice() else {
+        anyhow::bail!("--streaming converts exactly one input file");
+    };
+    let excludes = args.parse.excludes();
+    let options = args.parse.options(&args.settings)?;
+    let output = args.output.as_path();
+    let base_dir = args.parse.base_dir.as_path();
+    let stats = if file == Path::new("-") {
+        let lines = std::io::stdin().lock().lines();
+        lcov2xml::stream_to_file(
+            lines,
+            output,
+            base_dir,
+            &excludes,
+            &options,
+            now()?,
+            demangler,
+            &xml_options(args),
+        )?
+    } else {
+        let lines = std::io::BufReader::new(std::fs::File::open(file)?).lines();
+        lcov2xml::stream_to_file(
+            lines,
+            output,
+            base_dir,
+            &excludes,
+            &options,
+            now()?,
+            demangler,
+            &xml_options(args),
+        )?
+    };
+    args.parse.report_filtered(stats.filtered_records);
+    if args.total.total_line {
+        writeln!(
+            args.report_writer(),
+            "{}",
+            lcov2xml::summary_coverage_line(&stats.summary, args.total.total_first)
+        )?;
+    }
+    if args.split_xml {
+        lcov2xml::corbertura_xml_split(&args.output)?;
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Writes the merged records of all input files as one LCOV tracefile
+fn merge(args: &MergeArgs) -> anyhow::Result<ExitCode> {
+    let report = merge_reports(&args.input.files()?)?;
+    let mut output = args.output.writer()?;
+    for record in report.into_records() {
+        writeln!(output, "{record}")?;
+    }
+    output.flush()?;
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Prints the summary table and total line, then checks the thresholds
+fn summary(args: &SummaryArgs) -> anyhow::Result<ExitCode> {
+    let result = args.parse.read(&args.input, &args.settings)?;
+    let mut output = args.output.writer()?;
+    let table = lcov2xml::render_summary(&result, &args.table.options(args.format));
+    write!(output, "{table}")?;
+    if args.total.total_line {
+        let total = lcov2xml::total_coverage_line(&result, args.total.total_first);
+        writeln!(output, "{total}")?;
+    }
+    output.flush()?;
+    if !args.thresholds.check(&args.settings, &result)? {
+        return Ok(ExitCode::from(EXIT_BELOW_THRESHOLD));
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Prints the coverage of the changed lines
+fn diff(args: &DiffArgs) -> anyhow::Result<ExitCode> {
+    let result = args.parse.read(&args.input, &args.settings)?;
+    let mut output = args.output.writer()?;
+    let passed = check_diff(&result, &args.diff, args.fail_under_diff, &mut output)?;
+    output.flush()?;
+    if !passed {
+        return Ok(ExitCode::from(EXIT_BELOW_THRESHOLD));
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Parses every input file on its own and checks the merged report against the sources,
+/// fails if any file does not parse or has problems
+fn validate(args: &ValidateArgs) -> anyhow::Result<ExitCode> {
+    let files = args.input.files()?;
+    anyhow::ensure!(!files.is_empty(), "no filename given");
+    let excludes = args.parse.excludes();
+    let options = args.parse.options(&args.settings)?;
+    let mut merged: Option<lcov2xml::CoverageData> = None;
+    let mut valid = true;
+    for filename in &files {
+        match args.parse.parse_file(filename, &excludes, &options) {
+            Ok(result) => match &mut merged {
+                Some(merged) => merged.merge(result),
+                None => merged = Some(result),
+            },
+            Err(e) => {
+                eprintln!("{}: {e}", filename.display());
+                valid = false;
+            }
+        }
+    }
+    if let Some(merged) = merged {
+        args.parse.report_filtered(merged.filtered_records());
+        for problem in lcov2xml::validate(&merged)? {
+            eprintln!("{problem}");
+            valid = false;
+        }
+    }
+    if !valid {
+        return Ok(ExitCode::FAILURE);
+    }
+    println!("No problems found");
+    Ok(ExitCode::SUCCESS)
 }
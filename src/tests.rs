@@ -0,0 +1,61 @@
+#![allow(clippy::unwrap_used, reason = "if tests panic we see it on CI")]
+//! Tests of the subcommands
+use super::*;
+
+/// runs the command line `args` without reading a config file
+fn run(args: &[&str]) -> ExitCode {
+    let cli = Cli::try_parse_from(std::iter::once("lcov2xml").chain(args.iter().copied())).unwrap();
+    match &cli.command {
+        None => convert(&cli.convert),
+        Some(Command::Compare(args)) => compare(args),
+        Some(command) => panic!("not run by the tests: {command:?}"),
+    }
+    .unwrap()
+}
+
+#[test]
+fn test_compare_reads_lcov_like_convert() {
+    let dir = std::env::temp_dir().join(format!("lcov2xml-compare-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let base = dir.to_str().unwrap();
+    std::fs::write(
+        dir.join("before.info"),
+        format!("SF:{base}/src/a.rs\nDA:1,1\nDA:2,1\nend_of_record\n"),
+    )
+    .unwrap();
+    // the same file, covered in a container mounting the sources at `/container`
+    std::fs::write(
+        dir.join("after.info"),
+        "SF:/container/src/a.rs\nDA:1,1\nDA:2,0\nend_of_record\n",
+    )
+    .unwrap();
+    let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+    let before_xml = path("before.xml");
+    let after = path("after.info");
+    let path_map = format!("/container={base}");
+    assert_eq!(
+        run(&["-b", base, "-o", &before_xml, &path("before.info")]),
+        ExitCode::SUCCESS
+    );
+
+    let output = path("comparison.json");
+    let compare = |path_map: &[&str]| {
+        let mut args = vec!["compare", "-b", base, "--format", "json", "-o", &output];
+        args.extend(path_map);
+        args.extend([before_xml.as_str(), &after]);
+        let code = run(&args);
+        (code, std::fs::read_to_string(&output).unwrap())
+    };
+    let (code, json) = compare(&["--path-map", &path_map]);
+    assert_eq!(code, ExitCode::SUCCESS);
+    assert!(json.contains("\"src/a.rs\""), "{json}");
+    assert!(!json.contains("/container"), "{json}");
+    // without the mapping the file counts as removed and another one as added
+    let (_, json) = compare(&[]);
+    assert!(json.contains("/container/src/a.rs"), "{json}");
+
+    let (code, _) = compare(&["--path-map", &path_map, "--max-regression", "10"]);
+    assert_eq!(code, ExitCode::from(EXIT_BELOW_THRESHOLD));
+    std::fs::remove_dir_all(dir).unwrap();
+}